@@ -0,0 +1,82 @@
+//! Saving test regions of an existing map with structure blocks, to place them back afterwards
+//!
+//! A structure block is set above the region's minimum corner and triggered with a redstone
+//! block on top of it. The region is saved, block entities and entities included, as a
+//! structure that `/place template` puts back exactly.
+
+use crate::error::FlintError;
+
+/// Largest size along each axis a structure block saves
+pub const MAX_BACKUP_SIZE: i32 = 48;
+
+/// Fail when a region is larger than a structure block can save
+pub fn check_size(region: [[i32; 3]; 2]) -> Result<(), FlintError> {
+    let size: [i32; 3] = std::array::from_fn(|axis| (region[0][axis] - region[1][axis]).abs() + 1);
+    if size.iter().any(|length| *length > MAX_BACKUP_SIZE) {
+        return Err(FlintError::BackupTooLarge { size });
+    }
+    Ok(())
+}
+
+/// Structure id a region is saved as
+pub fn structure_name(test_idx: usize) -> String {
+    format!("flintmc:backup_{}", test_idx)
+}
+
+/// Where the structure block and the redstone block triggering it go, which must be air
+pub fn helper_positions(region: [[i32; 3]; 2]) -> [[i32; 3]; 2] {
+    let [min, max] = bounds(region);
+    let structure_block = [min[0], max[1] + 1, min[2]];
+    [
+        structure_block,
+        [
+            structure_block[0],
+            structure_block[1] + 1,
+            structure_block[2],
+        ],
+    ]
+}
+
+/// Commands that save a region and remove the helper blocks again
+pub fn save_commands(region: [[i32; 3]; 2], name: &str) -> Vec<String> {
+    let [min, max] = bounds(region);
+    let [structure_block, trigger] = helper_positions(region);
+    vec![
+        format!(
+            "setblock {} {} {} minecraft:structure_block[mode=save]{{mode:\"SAVE\",name:\"{}\",posX:0,posY:{},posZ:0,sizeX:{},sizeY:{},sizeZ:{},ignoreEntities:0b,showboundingbox:0b}}",
+            structure_block[0],
+            structure_block[1],
+            structure_block[2],
+            name,
+            min[1] - structure_block[1],
+            max[0] - min[0] + 1,
+            max[1] - min[1] + 1,
+            max[2] - min[2] + 1
+        ),
+        format!(
+            "setblock {} {} {} minecraft:redstone_block",
+            trigger[0], trigger[1], trigger[2]
+        ),
+        format!(
+            "setblock {} {} {} minecraft:air",
+            trigger[0], trigger[1], trigger[2]
+        ),
+        format!(
+            "setblock {} {} {} minecraft:air",
+            structure_block[0], structure_block[1], structure_block[2]
+        ),
+    ]
+}
+
+/// Command that places a saved region back
+pub fn restore_command(region: [[i32; 3]; 2], name: &str) -> String {
+    let [min, _] = bounds(region);
+    format!("place template {} {} {} {}", name, min[0], min[1], min[2])
+}
+
+fn bounds(region: [[i32; 3]; 2]) -> [[i32; 3]; 2] {
+    [
+        std::array::from_fn(|axis| region[0][axis].min(region[1][axis])),
+        std::array::from_fn(|axis| region[0][axis].max(region[1][axis])),
+    ]
+}
@@ -1,15 +1,46 @@
+use crate::error::FlintError;
 use anyhow::Result;
 use azalea::prelude::*;
-use parking_lot::RwLock;
+use azalea::protocol::packets::game::ClientboundGamePacket;
+use parking_lot::{Mutex as SyncMutex, RwLock};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, mpsc};
+
+/// Largest number of blocks a single `fill` may change
+const MAX_FILL_VOLUME: i64 = 32768;
+
+/// Pause after the server warns about spam, before sending at the reduced rate
+const SPAM_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Rate halved after the first spam warning when no limit was set
+const SPAM_FALLBACK_RATE: f64 = 20.0;
+
+/// Start of the message the bot sends itself to find where the server's replies to the
+/// commands before it end
+const REPLY_MARKER: &str = "flintmc-replied ";
+
+/// Time allowed for the server to answer the commands sent so far
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// View distance in chunks the bot asks the server for, azalea's default
+const CLIENT_VIEW_DISTANCE: u32 = 8;
+
+/// Length of a server tick at the normal tick rate
+const TICK: Duration = Duration::from_millis(50);
 
 #[derive(Clone, Component)]
 struct State {
     client_handle: Arc<RwLock<Option<Client>>>,
     in_game: Arc<AtomicBool>,
     chat_tx: Option<mpsc::UnboundedSender<String>>,
+    spam_warned: Arc<AtomicBool>,
+    rejections: Arc<SyncMutex<Vec<String>>>,
+    replied: Arc<AtomicU64>,
+    block_changes: Arc<SyncMutex<Option<Vec<BlockChange>>>>,
+    server_view_distance: Arc<AtomicU32>,
+    game_time: Arc<SyncMutex<Option<GameTime>>>,
 }
 
 impl Default for State {
@@ -18,15 +49,163 @@ impl Default for State {
             client_handle: Arc::new(RwLock::new(None)),
             in_game: Arc::new(AtomicBool::new(false)),
             chat_tx: None,
+            spam_warned: Arc::new(AtomicBool::new(false)),
+            rejections: Arc::new(SyncMutex::new(Vec::new())),
+            replied: Arc::new(AtomicU64::new(0)),
+            block_changes: Arc::new(SyncMutex::new(None)),
+            server_view_distance: Arc::new(AtomicU32::new(0)),
+            game_time: Arc::new(SyncMutex::new(None)),
         }
     }
 }
 
-#[derive(Default)]
+/// A block update from the server
+#[derive(Debug, Clone)]
+pub struct BlockChange {
+    pub pos: [i32; 3],
+    /// New block state, as azalea prints it
+    pub block: String,
+    /// Game time of the server tick the update was sent in, once the server has sent the time
+    pub tick: Option<u64>,
+}
+
+/// Game time the server last sent, which it does every 20 ticks, and when it arrived
+#[derive(Debug, Clone, Copy)]
+struct GameTime {
+    ticks: u64,
+    received: Instant,
+}
+
+impl GameTime {
+    /// Game time now, counting the ticks since the last time update
+    fn now(&self) -> u64 {
+        self.ticks + (self.received.elapsed().as_millis() / TICK.as_millis()) as u64
+    }
+}
+
+/// Token bucket limiting how fast commands are sent to the server
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Sustained commands per second, 0 for no limit
+    pub per_second: f64,
+    /// Commands that may be sent back to back before the limit applies
+    pub burst: u32,
+}
+
+/// Keeps the bot from flooding the server with commands. This is not below the spam kick for
+/// non-operators (about 10 messages back to back, 1 per second sustained): the bot has to be
+/// an operator, and operators are never kicked for spam.
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            per_second: 20.0,
+            burst: 40,
+        }
+    }
+}
+
+/// Commands waiting to be sent, and the rate limiter state
+struct CommandQueue {
+    /// `setblock`s not sent yet, merged into `fill`s when flushed
+    pending: Vec<([i32; 3], String)>,
+    rate: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+    /// Most recently sent command, named when the server reports a command error
+    last_command: Option<String>,
+    /// Dimension every command runs in through `execute in`, the bot's own when unset
+    dimension: Option<String>,
+}
+
+impl CommandQueue {
+    fn new(rate: RateLimit) -> Self {
+        Self {
+            pending: Vec::new(),
+            rate,
+            tokens: rate.burst as f64,
+            last_refill: Instant::now(),
+            last_command: None,
+            dimension: None,
+        }
+    }
+
+    /// Wait until the rate limit allows another command
+    async fn acquire(&mut self) {
+        if self.rate.per_second <= 0.0 {
+            return;
+        }
+        loop {
+            let now = Instant::now();
+            let refill = now.duration_since(self.last_refill).as_secs_f64() * self.rate.per_second;
+            self.tokens = (self.tokens + refill).min(self.rate.burst.max(1) as f64);
+            self.last_refill = now;
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                return;
+            }
+            let wait = (1.0 - self.tokens) / self.rate.per_second;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+
+    /// Halve the rate, pause and start again with an empty bucket after a spam warning
+    async fn back_off(&mut self) {
+        let current = if self.rate.per_second > 0.0 {
+            self.rate.per_second
+        } else {
+            SPAM_FALLBACK_RATE
+        };
+        self.rate.per_second = (current / 2.0).max(1.0);
+        tracing::warn!(
+            "Server warned about spam, slowing down to {} commands per second",
+            self.rate.per_second
+        );
+
+        tokio::time::sleep(SPAM_BACKOFF).await;
+        self.tokens = 0.0;
+        self.last_refill = Instant::now();
+    }
+}
+
 pub struct TestBot {
     client: Option<Arc<RwLock<Option<Client>>>>,
     in_game: Option<Arc<AtomicBool>>,
     chat_rx: Option<mpsc::UnboundedReceiver<String>>,
+    username: String,
+    connect_timeout: Duration,
+    queue: Mutex<CommandQueue>,
+    spam_warned: Arc<AtomicBool>,
+    /// Command errors the server sent since the last `check_commands`
+    rejections: Arc<SyncMutex<Vec<String>>>,
+    /// Reply markers sent, and the last one the server echoed back
+    markers: AtomicU64,
+    replied: Arc<AtomicU64>,
+    /// Block updates received since the last `take_block_changes`, while tracking
+    block_changes: Arc<SyncMutex<Option<Vec<BlockChange>>>>,
+    /// View distance the server sends chunks within, 0 until it tells the bot
+    server_view_distance: Arc<AtomicU32>,
+    /// Game time the server last sent, stamped on block updates
+    game_time: Arc<SyncMutex<Option<GameTime>>>,
+}
+
+impl Default for TestBot {
+    fn default() -> Self {
+        Self {
+            client: None,
+            in_game: None,
+            chat_rx: None,
+            username: "FlintMC_TestBot".to_string(),
+            connect_timeout: Duration::from_secs(15),
+            queue: Mutex::new(CommandQueue::new(RateLimit::default())),
+            spam_warned: Arc::new(AtomicBool::new(false)),
+            rejections: Arc::new(SyncMutex::new(Vec::new())),
+            markers: AtomicU64::new(0),
+            replied: Arc::new(AtomicU64::new(0)),
+            block_changes: Arc::new(SyncMutex::new(None)),
+            server_view_distance: Arc::new(AtomicU32::new(0)),
+            game_time: Arc::new(SyncMutex::new(None)),
+        }
+    }
 }
 
 impl TestBot {
@@ -34,8 +213,59 @@ impl TestBot {
         Self::default()
     }
 
+    pub fn set_username(&mut self, username: &str) {
+        self.username = username.to_string();
+    }
+
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout = timeout;
+    }
+
+    pub fn set_rate_limit(&mut self, rate: RateLimit) {
+        *self.queue.get_mut() = CommandQueue::new(rate);
+    }
+
+    /// Start or stop collecting block updates from the server, dropping any collected
+    pub fn track_block_changes(&self, enabled: bool) {
+        *self.block_changes.lock() = enabled.then(Vec::new);
+    }
+
+    /// Block updates received since the last call, while tracking
+    pub fn take_block_changes(&self) -> Vec<BlockChange> {
+        self.block_changes
+            .lock()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// The server's game time, once it has sent it
+    pub fn game_time(&self) -> Option<u64> {
+        self.game_time.lock().map(|time| time.now())
+    }
+
+    /// Chunks around the bot, in each direction, that it receives block updates for
+    pub fn view_distance(&self) -> u32 {
+        match self.server_view_distance.load(Ordering::SeqCst) {
+            0 => CLIENT_VIEW_DISTANCE,
+            server => server.min(CLIENT_VIEW_DISTANCE),
+        }
+    }
+
+    /// Run every following command in a dimension, including the `tp` of `observe_from`
+    pub async fn set_dimension(&self, dimension: &str) {
+        self.queue.lock().await.dimension = Some(dimension.to_string());
+    }
+
+    /// Switch the bot to spectator mode and move it to a position
+    pub async fn observe_from(&self, pos: [i32; 3]) -> Result<()> {
+        self.send_command("gamemode spectator").await?;
+        self.send_command(&format!("tp {} {} {}", pos[0], pos[1], pos[2]))
+            .await
+    }
+
     pub async fn connect(&mut self, server: &str) -> Result<()> {
-        let account = Account::offline("FlintMC_TestBot");
+        let account = Account::offline(&self.username);
 
         tracing::info!("Connecting to server: {}", server);
 
@@ -44,6 +274,12 @@ impl TestBot {
 
         let state = State {
             chat_tx: Some(chat_tx),
+            spam_warned: self.spam_warned.clone(),
+            rejections: self.rejections.clone(),
+            replied: self.replied.clone(),
+            block_changes: self.block_changes.clone(),
+            server_view_distance: self.server_view_distance.clone(),
+            game_time: self.game_time.clone(),
             ..Default::default()
         };
         let client_handle = state.client_handle.clone();
@@ -66,10 +302,39 @@ impl TestBot {
                     Event::Chat(m) => {
                         // Extract the message content and send it through the channel
                         let message = m.message().to_string();
+                        if let Some(marker) = reply_marker(&message) {
+                            state.replied.store(marker, Ordering::SeqCst);
+                            return Ok(());
+                        }
+                        if is_spam_warning(&message) {
+                            state.spam_warned.store(true, Ordering::SeqCst);
+                        }
+                        if is_command_error(&message) {
+                            state.rejections.lock().push(message.clone());
+                        }
                         if let Some(ref tx) = state.chat_tx {
                             let _ = tx.send(message);
                         }
                     }
+                    Event::Packet(packet) => {
+                        if let ClientboundGamePacket::SetTime(time) = packet.as_ref() {
+                            *state.game_time.lock() = Some(GameTime {
+                                ticks: time.game_time,
+                                received: Instant::now(),
+                            });
+                        }
+                        if let Some(changes) = state.block_changes.lock().as_mut() {
+                            let tick = state.game_time.lock().map(|time| time.now());
+                            changes.extend(
+                                block_changes(&packet)
+                                    .into_iter()
+                                    .map(|(pos, block)| BlockChange { pos, block, tick }),
+                            );
+                        }
+                        if let Some(distance) = view_distance(&packet) {
+                            state.server_view_distance.store(distance, Ordering::SeqCst);
+                        }
+                    }
                     _ => {}
                 }
                 Ok(())
@@ -87,7 +352,8 @@ impl TestBot {
         });
 
         // Wait for client to initialize
-        for _ in 0..50 {
+        let deadline = std::time::Instant::now() + self.connect_timeout;
+        while std::time::Instant::now() < deadline {
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
             if client_handle.read().is_some() {
                 break;
@@ -95,12 +361,16 @@ impl TestBot {
         }
 
         if client_handle.read().is_none() {
-            anyhow::bail!("Failed to initialize bot connection");
+            return Err(FlintError::Connection(format!(
+                "bot did not initialize within {}s",
+                self.connect_timeout.as_secs()
+            ))
+            .into());
         }
 
         // Wait for bot to be in game state
         tracing::info!("Waiting for bot to enter game state...");
-        for _ in 0..100 {
+        while std::time::Instant::now() < deadline {
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
             if in_game.load(Ordering::SeqCst) {
                 break;
@@ -108,7 +378,11 @@ impl TestBot {
         }
 
         if !in_game.load(Ordering::SeqCst) {
-            anyhow::bail!("Bot failed to enter game state within timeout");
+            return Err(FlintError::Timeout {
+                what: "the bot to enter the game".to_string(),
+                after: self.connect_timeout,
+            }
+            .into());
         }
 
         self.client = Some(client_handle);
@@ -134,27 +408,143 @@ impl TestBot {
         }
     }
 
+    /// Queue a `setblock`; consecutive ones with the same block are merged into `fill`s
+    /// and sent before the next command or block read
+    pub async fn queue_setblock(&self, pos: [i32; 3], block: &str) -> Result<()> {
+        if self.client.is_none() {
+            return Err(FlintError::Connection("bot not connected".to_string()).into());
+        }
+        self.queue
+            .lock()
+            .await
+            .pending
+            .push((pos, block.to_string()));
+        Ok(())
+    }
+
+    /// Send all queued `setblock`s
+    pub async fn flush_commands(&self) -> Result<()> {
+        let mut queue = self.queue.lock().await;
+        self.flush_pending(&mut queue).await
+    }
+
+    /// Send all queued `setblock`s and wait until the server has answered every command sent
+    /// so far, failing if it rejected any of those sent since the last check
+    ///
+    /// The server answers commands in order, so everything it says before the bot's own
+    /// marker message is about the commands before it.
+    pub async fn check_commands(&self) -> Result<()> {
+        let mut queue = self.queue.lock().await;
+        self.flush_pending(&mut queue).await?;
+        let last_command = queue.last_command.clone();
+
+        let marker = self.markers.fetch_add(1, Ordering::SeqCst) + 1;
+        self.send_limited(
+            &mut queue,
+            &format!("tellraw @s \"{}{}\"", REPLY_MARKER, marker),
+        )
+        .await?;
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        while self.replied.load(Ordering::SeqCst) < marker {
+            if Instant::now() >= deadline {
+                return Err(FlintError::Timeout {
+                    what: "the server to answer the commands sent".to_string(),
+                    after: REPLY_TIMEOUT,
+                }
+                .into());
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let rejections = std::mem::take(&mut *self.rejections.lock());
+        match rejections.into_iter().next() {
+            Some(message) => Err(FlintError::CommandRejected {
+                command: last_command.unwrap_or_default(),
+                message,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    async fn flush_pending(&self, queue: &mut CommandQueue) -> Result<()> {
+        let pending = std::mem::take(&mut queue.pending);
+        for command in merge_setblocks(&pending) {
+            self.send_limited(queue, &command).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a command after any queued `setblock`s, respecting the rate limit
     pub async fn send_command(&self, command: &str) -> Result<()> {
+        let mut queue = self.queue.lock().await;
+        self.flush_pending(&mut queue).await?;
+        self.send_limited(&mut queue, command).await
+    }
+
+    async fn send_limited(&self, queue: &mut CommandQueue, command: &str) -> Result<()> {
+        if self.spam_warned.swap(false, Ordering::SeqCst) {
+            queue.back_off().await;
+        }
+        queue.acquire().await;
+
         if let Some(client_handle) = &self.client {
             if let Some(client) = client_handle.read().as_ref() {
                 // Add "/" prefix if not present
-                let command_with_slash = if command.starts_with('/') {
-                    command.to_string()
-                } else {
-                    format!("/{}", command)
+                let command = command.strip_prefix('/').unwrap_or(command);
+                let command_with_slash = match &queue.dimension {
+                    Some(dimension) => format!("/execute in {} run {}", dimension, command),
+                    None => format!("/{}", command),
                 };
                 tracing::debug!("Sending command: {}", command_with_slash);
                 client.chat(&command_with_slash);
+                queue.last_command = Some(command_with_slash);
                 Ok(())
             } else {
-                anyhow::bail!("Bot not initialized")
+                Err(FlintError::Connection("bot not initialized".to_string()).into())
             }
         } else {
-            anyhow::bail!("Bot not connected")
+            Err(FlintError::Connection("bot not connected".to_string()).into())
+        }
+    }
+
+    /// Send commands at once from code that cannot wait, skipping the queue and the rate
+    /// limit. Returns false if the bot is not connected.
+    pub fn send_unqueued(&self, commands: &[String]) -> bool {
+        let Some(client_handle) = &self.client else {
+            return false;
+        };
+        let client = client_handle.read();
+        let Some(client) = client.as_ref() else {
+            return false;
+        };
+        let dimension = self
+            .queue
+            .try_lock()
+            .ok()
+            .and_then(|queue| queue.dimension.clone());
+        for command in commands {
+            let command = command.strip_prefix('/').unwrap_or(command);
+            match &dimension {
+                Some(dimension) => {
+                    client.chat(&format!("/execute in {} run {}", dimension, command))
+                }
+                None => client.chat(&format!("/{}", command)),
+            }
         }
+        true
     }
 
-    pub async fn get_block(&self, pos: [i32; 3]) -> Result<Option<String>> {
+    /// Block state at a world position, failing if the bot has no chunk data there
+    pub async fn get_block(&self, pos: [i32; 3]) -> Result<String> {
+        self.block_if_loaded(pos)
+            .await?
+            .ok_or_else(|| FlintError::ChunkNotLoaded { pos }.into())
+    }
+
+    /// Block state at a world position, or `None` where the bot has no chunk data
+    pub async fn block_if_loaded(&self, pos: [i32; 3]) -> Result<Option<String>> {
+        self.flush_commands().await?;
         if let Some(client_handle) = &self.client {
             if let Some(client) = client_handle.read().as_ref() {
                 let block_pos = azalea::BlockPos::new(pos[0], pos[1], pos[2]);
@@ -170,10 +560,10 @@ impl TestBot {
                     Ok(None)
                 }
             } else {
-                anyhow::bail!("Bot not initialized")
+                Err(FlintError::Connection("bot not initialized".to_string()).into())
             }
         } else {
-            anyhow::bail!("Bot not connected")
+            Err(FlintError::Connection("bot not connected".to_string()).into())
         }
     }
 
@@ -182,6 +572,7 @@ impl TestBot {
         pos: [i32; 3],
         property: &str,
     ) -> Result<Option<String>> {
+        self.flush_commands().await?;
         if let Some(client_handle) = &self.client {
             if let Some(client) = client_handle.read().as_ref() {
                 let block_pos = azalea::BlockPos::new(pos[0], pos[1], pos[2]);
@@ -202,13 +593,275 @@ impl TestBot {
                         Ok(None)
                     }
                 } else {
-                    Ok(None)
+                    Err(FlintError::ChunkNotLoaded { pos }.into())
                 }
             } else {
-                anyhow::bail!("Bot not initialized")
+                Err(FlintError::Connection("bot not initialized".to_string()).into())
             }
         } else {
-            anyhow::bail!("Bot not connected")
+            Err(FlintError::Connection("bot not connected".to_string()).into())
+        }
+    }
+}
+
+/// Server messages that mean commands are arriving too fast. Player chat (`<name> ...`) and
+/// `/say` output (`[name] ...`) are ignored so test names cannot trigger a backoff.
+fn is_spam_warning(message: &str) -> bool {
+    let message = message.trim();
+    if message.starts_with('<') || message.starts_with('[') {
+        return false;
+    }
+    let lower = message.to_lowercase();
+    lower.contains("spam") || lower.contains("too fast") || lower.contains("slow down")
+}
+
+/// Positions and new states of the blocks changed by a block update packet
+fn block_changes(packet: &ClientboundGamePacket) -> Vec<([i32; 3], String)> {
+    match packet {
+        ClientboundGamePacket::BlockUpdate(update) => vec![(
+            [update.pos.x, update.pos.y, update.pos.z],
+            format!("{:?}", update.block_state),
+        )],
+        ClientboundGamePacket::SectionBlocksUpdate(update) => {
+            let section = update.section_pos;
+            update
+                .states
+                .iter()
+                .map(|change| {
+                    (
+                        [
+                            section.x * 16 + change.pos.x as i32,
+                            section.y * 16 + change.pos.y as i32,
+                            section.z * 16 + change.pos.z as i32,
+                        ],
+                        format!("{:?}", change.state),
+                    )
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// View distance the server announces on login or changes later
+fn view_distance(packet: &ClientboundGamePacket) -> Option<u32> {
+    match packet {
+        ClientboundGamePacket::Login(login) => Some(login.chunk_radius),
+        ClientboundGamePacket::SetChunkCacheRadius(radius) => Some(radius.radius),
+        _ => None,
+    }
+}
+
+/// Number of the bot's reply marker, if the message is one
+fn reply_marker(message: &str) -> Option<u64> {
+    message.trim().strip_prefix(REPLY_MARKER)?.parse().ok()
+}
+
+/// Server messages that mean the last commands failed. Like spam warnings, messages
+/// from players and `/say` are ignored.
+fn is_command_error(message: &str) -> bool {
+    let message = message.trim();
+    if message.starts_with('<') || message.starts_with('[') {
+        return false;
+    }
+    [
+        "Unknown or incomplete command",
+        "Incorrect argument for command",
+        "<--[HERE]",
+        "Unknown block type",
+        "That position is not loaded",
+        "Too many blocks in the specified area",
+    ]
+    .iter()
+    .any(|error| message.contains(error))
+}
+
+/// Turn queued `setblock`s into commands. Each run of consecutive placements of the same
+/// block becomes one `fill` if it forms a cuboid, otherwise one `fill` per row along X.
+/// Runs are kept in order, so placements of different blocks never swap.
+fn merge_setblocks(pending: &[([i32; 3], String)]) -> Vec<String> {
+    let mut commands = Vec::new();
+
+    for run in pending.chunk_by(|a, b| a.1 == b.1) {
+        let block = &run[0].1;
+        let mut positions: Vec<[i32; 3]> = run.iter().map(|(pos, _)| *pos).collect();
+        positions.sort_by_key(|pos| (pos[1], pos[2], pos[0]));
+        positions.dedup();
+
+        if positions.len() > 1
+            && let Some([min, max]) = cuboid(&positions)
+        {
+            commands.push(fill_command(min, max, block));
+            continue;
         }
+
+        // Rows along X: positions are sorted by (y, z, x)
+        for row in positions.chunk_by(|a, b| a[1] == b[1] && a[2] == b[2] && b[0] == a[0] + 1) {
+            let (min, max) = (row[0], row[row.len() - 1]);
+            if min == max {
+                commands.push(format!(
+                    "setblock {} {} {} {}",
+                    min[0], min[1], min[2], block
+                ));
+            } else {
+                commands.push(fill_command(min, max, block));
+            }
+        }
+    }
+
+    commands
+}
+
+/// The bounding box of distinct positions, if they fill it completely and one `fill` can cover it
+pub fn cuboid(positions: &[[i32; 3]]) -> Option<[[i32; 3]; 2]> {
+    let mut min = *positions.first()?;
+    let mut max = min;
+    for pos in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(pos[axis]);
+            max[axis] = max[axis].max(pos[axis]);
+        }
+    }
+    let volume = (0..3)
+        .map(|axis| (max[axis] - min[axis] + 1) as i64)
+        .product::<i64>();
+    (volume == positions.len() as i64 && volume <= MAX_FILL_VOLUME).then_some([min, max])
+}
+
+/// `forceload add` or `forceload remove` commands covering a region, each within the
+/// server's limit of 256 chunks
+pub fn forceload_commands(region: [[i32; 3]; 2], action: &str) -> Vec<String> {
+    let (min, max) = region_chunks(region);
+
+    let mut commands = Vec::new();
+    for chunk_x in (min[0]..=max[0]).step_by(16) {
+        for chunk_z in (min[1]..=max[1]).step_by(16) {
+            let last_x = (chunk_x + 15).min(max[0]);
+            let last_z = (chunk_z + 15).min(max[1]);
+            commands.push(format!(
+                "forceload {} {} {} {} {}",
+                action,
+                chunk_x * 16,
+                chunk_z * 16,
+                last_x * 16 + 15,
+                last_z * 16 + 15
+            ));
+        }
+    }
+    commands
+}
+
+/// First and last chunk, as `[x, z]`, covering a region in world coordinates
+pub fn region_chunks(region: [[i32; 3]; 2]) -> ([i32; 2], [i32; 2]) {
+    let chunk = |axis: usize, pick: fn(i32, i32) -> i32| {
+        pick(region[0][axis], region[1][axis]).div_euclid(16)
+    };
+    (
+        [chunk(0, i32::min), chunk(2, i32::min)],
+        [chunk(0, i32::max), chunk(2, i32::max)],
+    )
+}
+
+/// `fill` commands covering a region of any size, each within the server's volume limit
+pub fn region_fill_commands(region: [[i32; 3]; 2], block: &str) -> Vec<String> {
+    let min: [i32; 3] = std::array::from_fn(|axis| region[0][axis].min(region[1][axis]));
+    let max: [i32; 3] = std::array::from_fn(|axis| region[0][axis].max(region[1][axis]));
+
+    // 32x32 columns, as tall as the limit allows
+    let step_x = (max[0] - min[0] + 1).min(32);
+    let step_z = (max[2] - min[2] + 1).min(32);
+    let step_y = (MAX_FILL_VOLUME / (step_x * step_z) as i64).max(1) as i32;
+
+    let mut commands = Vec::new();
+    for y in (min[1]..=max[1]).step_by(step_y as usize) {
+        for z in (min[2]..=max[2]).step_by(step_z as usize) {
+            for x in (min[0]..=max[0]).step_by(step_x as usize) {
+                let box_max = [
+                    (x + step_x - 1).min(max[0]),
+                    (y + step_y - 1).min(max[1]),
+                    (z + step_z - 1).min(max[2]),
+                ];
+                commands.push(fill_command([x, y, z], box_max, block));
+            }
+        }
+    }
+    commands
+}
+
+fn fill_command(min: [i32; 3], max: [i32; 3], block: &str) -> String {
+    format!(
+        "fill {} {} {} {} {} {} {}",
+        min[0], min[1], min[2], max[0], max[1], max[2], block
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placements(blocks: &[([i32; 3], &str)]) -> Vec<([i32; 3], String)> {
+        blocks
+            .iter()
+            .map(|(pos, block)| (*pos, block.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn cuboid_becomes_one_fill() {
+        let pending = placements(&[
+            ([0, 0, 0], "minecraft:stone"),
+            ([1, 0, 0], "minecraft:stone"),
+            ([0, 0, 1], "minecraft:stone"),
+            ([1, 0, 1], "minecraft:stone"),
+        ]);
+        assert_eq!(
+            merge_setblocks(&pending),
+            vec!["fill 0 0 0 1 0 1 minecraft:stone"]
+        );
+    }
+
+    #[test]
+    fn other_shapes_become_rows_along_x() {
+        let pending = placements(&[
+            ([0, 0, 0], "minecraft:stone"),
+            ([1, 0, 0], "minecraft:stone"),
+            ([0, 0, 1], "minecraft:stone"),
+        ]);
+        assert_eq!(
+            merge_setblocks(&pending),
+            vec![
+                "fill 0 0 0 1 0 0 minecraft:stone",
+                "setblock 0 0 1 minecraft:stone"
+            ]
+        );
+    }
+
+    #[test]
+    fn placements_of_different_blocks_keep_their_order() {
+        let pending = placements(&[
+            ([0, 0, 0], "minecraft:stone"),
+            ([1, 0, 0], "minecraft:dirt"),
+            ([2, 0, 0], "minecraft:stone"),
+        ]);
+        assert_eq!(
+            merge_setblocks(&pending),
+            vec![
+                "setblock 0 0 0 minecraft:stone",
+                "setblock 1 0 0 minecraft:dirt",
+                "setblock 2 0 0 minecraft:stone"
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_placement_is_sent_once() {
+        let pending = placements(&[
+            ([4, 5, 6], "minecraft:stone"),
+            ([4, 5, 6], "minecraft:stone"),
+        ]);
+        assert_eq!(
+            merge_setblocks(&pending),
+            vec!["setblock 4 5 6 minecraft:stone"]
+        );
     }
 }
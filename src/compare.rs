@@ -0,0 +1,180 @@
+use crate::executor::Observation;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use flint_core::results::TestResult;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Shown for a value one server never read, e.g. because its test stopped early
+const NOT_OBSERVED: &str = "(not observed)";
+
+/// Shown for a position the bot had no chunk data for
+const UNLOADED: &str = "(unloaded)";
+
+type ObservationKey = (String, u32, [i32; 3], Option<String>);
+
+/// One position, tick and property where the two servers read different values
+pub struct Difference {
+    pub test: String,
+    pub tick: u32,
+    pub pos: [i32; 3],
+    pub property: Option<String>,
+    pub left: String,
+    pub right: String,
+}
+
+fn by_key(observations: &[Observation]) -> BTreeMap<ObservationKey, String> {
+    observations
+        .iter()
+        .map(|observation| {
+            (
+                (
+                    observation.test.clone(),
+                    observation.tick,
+                    observation.pos,
+                    observation.property.clone(),
+                ),
+                observation
+                    .value
+                    .clone()
+                    .unwrap_or_else(|| UNLOADED.to_string()),
+            )
+        })
+        .collect()
+}
+
+/// Every observation whose value differs between the two servers, ordered by test, tick and position
+pub fn diff(left: &[Observation], right: &[Observation]) -> Vec<Difference> {
+    let left = by_key(left);
+    let right = by_key(right);
+    let keys: BTreeSet<&ObservationKey> = left.keys().chain(right.keys()).collect();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let left_value = left.get(key).map_or(NOT_OBSERVED, String::as_str);
+            let right_value = right.get(key).map_or(NOT_OBSERVED, String::as_str);
+            (left_value != right_value).then(|| Difference {
+                test: key.0.clone(),
+                tick: key.1,
+                pos: key.2,
+                property: key.3.clone(),
+                left: left_value.to_string(),
+                right: right_value.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Tests that passed on one server and failed on the other
+pub fn status_changes<'a>(
+    left: &'a [TestResult],
+    right: &'a [TestResult],
+) -> Vec<(&'a str, bool, bool)> {
+    left.iter()
+        .filter_map(|left_result| {
+            let right_result = right
+                .iter()
+                .find(|result| result.test_name == left_result.test_name)?;
+            (left_result.success != right_result.success).then_some((
+                left_result.test_name.as_str(),
+                left_result.success,
+                right_result.success,
+            ))
+        })
+        .collect()
+}
+
+pub fn print_comparison(
+    servers: [&str; 2],
+    results: [&[TestResult]; 2],
+    differences: &[Difference],
+) {
+    println!("\n{}", "═".repeat(60).dimmed());
+    println!("{}", "Comparison".cyan().bold());
+    println!("{}", "═".repeat(60).dimmed());
+
+    for (server, server_results) in servers.iter().zip(results) {
+        let passed = server_results.iter().filter(|r| r.success).count();
+        println!(
+            "  {} {}: {}/{} passed",
+            "→".blue(),
+            server,
+            passed,
+            server_results.len()
+        );
+    }
+
+    let changes = status_changes(results[0], results[1]);
+    if !changes.is_empty() {
+        println!("\n  {}", "Tests with a different outcome:".bold());
+        for (test, left, right) in &changes {
+            let status = |success: bool| {
+                if success {
+                    "PASS".green()
+                } else {
+                    "FAIL".red()
+                }
+            };
+            println!("    {} {} / {}", test, status(*left), status(*right));
+        }
+    }
+
+    if differences.is_empty() {
+        println!(
+            "\n{} Both servers observed identical states at every assertion\n",
+            "✓".green().bold()
+        );
+        return;
+    }
+
+    println!(
+        "\n  {} {} observed state(s) differ:",
+        "✗".red().bold(),
+        differences.len()
+    );
+    let mut current_test = None;
+    for difference in differences {
+        if current_test != Some(&difference.test) {
+            println!("\n    [{}]", difference.test.bold());
+            current_test = Some(&difference.test);
+        }
+        let property = difference
+            .property
+            .as_ref()
+            .map(|property| format!(" {}", property))
+            .unwrap_or_default();
+        println!(
+            "      Tick {} [{}, {}, {}]{}:",
+            difference.tick, difference.pos[0], difference.pos[1], difference.pos[2], property
+        );
+        println!("        {}: {}", servers[0], difference.left.yellow());
+        println!("        {}: {}", servers[1], difference.right.cyan());
+    }
+    println!();
+}
+
+pub fn write_json_report(
+    path: &Path,
+    servers: [&str; 2],
+    differences: &[Difference],
+) -> Result<()> {
+    let report = json!({
+        "servers": servers,
+        "differences": differences
+            .iter()
+            .map(|difference| {
+                json!({
+                    "test": difference.test,
+                    "tick": difference.tick,
+                    "pos": difference.pos,
+                    "property": difference.property,
+                    "values": [difference.left, difference.right],
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+
+    std::fs::write(path, serde_json::to_string_pretty(&report)?)
+        .with_context(|| format!("Failed to write comparison report {}", path.display()))
+}
@@ -0,0 +1,323 @@
+use crate::bot::RateLimit;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = "flintmc.toml";
+
+/// Project configuration loaded from `flintmc.toml`
+///
+/// Every value is a default; command-line flags always take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Default server address
+    pub server: Option<String>,
+    /// Test roots used when neither a path nor tags are given on the command line
+    pub paths: Vec<PathBuf>,
+    /// Recursively search test roots
+    pub recursive: bool,
+    /// Default tag filter
+    pub tags: Vec<String>,
+    /// How results are reported after a run
+    pub reporter: Reporter,
+    /// Where the JSON reporter writes its report
+    pub report_path: Option<PathBuf>,
+    /// How often failed tests are re-run alone before they count as failed
+    pub retries: u32,
+    /// Tests whose results are reported but never fail the run
+    pub quarantine: Vec<String>,
+    /// Running on an existing map: test areas are saved first and placed back afterwards
+    pub preserve_world: bool,
+    pub bot: BotConfig,
+    pub layout: LayoutConfig,
+    pub debugger: DebuggerConfig,
+    pub polling: PollingConfig,
+    pub timeouts: TimeoutConfig,
+    pub commands: CommandConfig,
+    pub managed_server: ManagedServerConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Reporter {
+    /// Colored summary in the terminal
+    #[default]
+    Text,
+    /// Terminal summary plus a machine-readable JSON report file
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    pub username: String,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            username: "FlintMC_TestBot".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebuggerConfig {
+    /// Players who may send server commands with `run` from chat, which the bot runs as an
+    /// operator. The terminal can always use `run`.
+    pub run_players: Vec<String>,
+}
+
+/// Where tests are placed in the world
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Distance in blocks between neighbouring tests on a fixed grid, instead of packing them
+    pub spacing: Option<i32>,
+    /// Blocks left free between the cleanup regions of packed tests
+    pub gap: i32,
+    /// World X and Z of the corner the layout starts from
+    pub origin: [i32; 2],
+    /// Y the bottom of every cleanup region is moved to, tests keep their own Y when unset
+    pub y: Option<i32>,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            spacing: None,
+            gap: 8,
+            origin: [0, 0],
+            y: None,
+        }
+    }
+}
+
+/// How often assertions re-read a block before giving up
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollingConfig {
+    pub attempts: u32,
+    pub delay_ms: u64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 10,
+            delay_ms: 50,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// Time allowed for the bot to join and enter the game state
+    pub connect_secs: u64,
+    /// Time allowed for a `/tick sprint` to report completion
+    pub sprint_secs: u64,
+    /// Time allowed for the bot to receive the chunks of forceloaded test regions
+    pub chunk_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: 15,
+            sprint_secs: 30,
+            chunk_secs: 10,
+        }
+    }
+}
+
+/// Server jar started by `flintmc run --server-jar`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ManagedServerConfig {
+    /// Jar to start when no server address is given
+    pub jar: Option<PathBuf>,
+    /// Working directory of the server: world, logs and generated server.properties
+    pub dir: PathBuf,
+    pub java: String,
+    pub jvm_args: Vec<String>,
+    /// Port to listen on, a free one is picked when unset
+    pub port: Option<u16>,
+    /// Time allowed for the server to finish starting
+    pub startup_secs: u64,
+}
+
+impl Default for ManagedServerConfig {
+    fn default() -> Self {
+        Self {
+            jar: None,
+            dir: PathBuf::from("flintmc-server"),
+            java: "java".to_string(),
+            jvm_args: vec!["-Xmx2G".to_string()],
+            port: None,
+            startup_secs: 120,
+        }
+    }
+}
+
+/// How fast commands are sent, so large setups do not flood the server
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandConfig {
+    /// Sustained commands per second, 0 for no limit
+    pub per_second: f64,
+    /// Commands that may be sent back to back
+    pub burst: u32,
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        let rate = RateLimit::default();
+        Self {
+            per_second: rate.per_second,
+            burst: rate.burst,
+        }
+    }
+}
+
+impl CommandConfig {
+    pub fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            per_second: self.per_second,
+            burst: self.burst,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration, either from an explicit file or by searching up from the
+    /// current directory. Returns the default configuration when no file is found.
+    pub fn load(explicit: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        let path = match explicit {
+            Some(path) => Some(path.to_path_buf()),
+            None => Self::find(&std::env::current_dir()?),
+        };
+
+        let Some(path) = path else {
+            if let Some(profile) = profile {
+                anyhow::bail!(
+                    "Profile '{}' requested but no {} was found",
+                    profile,
+                    CONFIG_FILE_NAME
+                );
+            }
+            return Ok(Self::default());
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut table: toml::Table =
+            toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        // Profiles are overlays on the top-level table, e.g. [profile.ci]
+        let profiles = table.remove("profile");
+        if let Some(profile) = profile {
+            let overlay = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(profile))
+                .and_then(|overlay| overlay.as_table())
+                .with_context(|| {
+                    format!("Profile '{}' is not defined in {}", profile, path.display())
+                })?;
+            merge(&mut table, overlay.clone());
+        }
+
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Invalid configuration in {}", path.display()))?;
+
+        // Test roots are relative to the directory holding the config file
+        if let Some(base_dir) = path.parent() {
+            config.paths = config
+                .paths
+                .into_iter()
+                .map(|root| base_dir.join(root))
+                .collect();
+            config.report_path = config.report_path.map(|report| base_dir.join(report));
+            config.managed_server.jar = config.managed_server.jar.map(|jar| base_dir.join(jar));
+            config.managed_server.dir = base_dir.join(&config.managed_server.dir);
+        }
+
+        tracing::info!("Loaded configuration from {}", path.display());
+        Ok(config)
+    }
+
+    /// Search `start` and its ancestors for a config file
+    fn find(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+/// Recursively overlay `overlay` onto `base`, replacing non-table values
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match value {
+            toml::Value::Table(overlay_table)
+                if base.get(&key).is_some_and(toml::Value::is_table) =>
+            {
+                if let Some(toml::Value::Table(base_table)) = base.get_mut(&key) {
+                    merge(base_table, overlay_table);
+                }
+            }
+            value => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> toml::Table {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn profile_overrides_only_the_keys_it_sets() {
+        let mut base = table("server = \"a:1\"\n[polling]\nattempts = 10\ndelay_ms = 50\n");
+        merge(&mut base, table("[polling]\nattempts = 20\n"));
+
+        assert_eq!(
+            base.get("server").and_then(toml::Value::as_str),
+            Some("a:1")
+        );
+        let polling = &base["polling"];
+        assert_eq!(
+            polling.get("attempts").and_then(toml::Value::as_integer),
+            Some(20)
+        );
+        assert_eq!(
+            polling.get("delay_ms").and_then(toml::Value::as_integer),
+            Some(50)
+        );
+    }
+
+    #[test]
+    fn profile_replaces_arrays_and_adds_tables() {
+        let mut base = table("tags = [\"slow\"]\n");
+        merge(
+            &mut base,
+            table("tags = [\"fast\"]\n[bot]\nusername = \"Ci\"\n"),
+        );
+
+        let tags = base["tags"].as_array().unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].as_str(), Some("fast"));
+        assert_eq!(
+            base["bot"].get("username").and_then(toml::Value::as_str),
+            Some("Ci")
+        );
+    }
+}
@@ -0,0 +1,687 @@
+//! Debug Adapter Protocol server for `flintmc dap`
+//!
+//! The adapter speaks DAP over stdio and runs the tests in a child `flintmc run` process.
+//! Stepping reuses the breakpoint console: DAP requests are written to the child's stdin
+//! as console commands, and the child reports every stop as a machine-readable line.
+
+use crate::debugger::STOP_EVENT_PREFIX;
+use anyhow::{Context, Result};
+use flint_core::loader::TestLoader;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc;
+
+/// The only thread exposed to the editor: the merged test timeline
+const THREAD_ID: i64 = 1;
+const WATCHES_REFERENCE: i64 = 1;
+const TESTS_REFERENCE: i64 = 2;
+
+/// Time the test run gets to unfreeze and clean up after an interrupt before it is killed
+const CHILD_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Arguments of the `launch` request, as set in the editor's launch configuration
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct LaunchArgs {
+    /// Test file or directory
+    program: Option<PathBuf>,
+    server: Option<String>,
+    recursive: bool,
+    tags: Vec<String>,
+    /// Break after setup, before tick 0 runs
+    stop_on_entry: bool,
+}
+
+enum Incoming {
+    Request(Value),
+    ClientClosed,
+    Stdout(String),
+    Stderr(String),
+    ChildExited,
+}
+
+/// Timeline entries of one test file, by the line each entry starts on
+struct SourceMap {
+    test_name: String,
+    /// (line, tick) for every timeline entry, in file order
+    entries: Vec<(i64, u32)>,
+}
+
+impl SourceMap {
+    fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let spec: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let ticks = spec["timeline"]
+            .as_array()
+            .map(|timeline| {
+                timeline
+                    .iter()
+                    .map(|entry| match &entry["at"] {
+                        Value::Array(ticks) => ticks.first().and_then(Value::as_u64),
+                        at => at.as_u64(),
+                    })
+                    .map(|tick| tick.unwrap_or(0) as u32)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            test_name: spec["name"].as_str().unwrap_or_default().to_string(),
+            entries: timeline_entry_lines(&text).into_iter().zip(ticks).collect(),
+        })
+    }
+
+    /// The entry a breakpoint on `line` belongs to: the one containing it, or the first one
+    fn entry_for_line(&self, line: i64) -> Option<(i64, u32)> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= line)
+            .or_else(|| self.entries.first())
+            .copied()
+    }
+
+    /// Line of the last entry that has run before `next_tick`
+    fn line_for_tick(&self, next_tick: u32) -> i64 {
+        self.entries
+            .iter()
+            .filter(|(_, tick)| *tick < next_tick)
+            .max_by_key(|(_, tick)| *tick)
+            .or_else(|| self.entries.first())
+            .map(|(line, _)| *line)
+            .unwrap_or(1)
+    }
+}
+
+/// 1-based line on which each object of the top-level `timeline` array starts
+fn timeline_entry_lines(text: &str) -> Vec<i64> {
+    let Some(start) = text.find("\"timeline\"") else {
+        return Vec::new();
+    };
+
+    let mut lines = Vec::new();
+    let mut line = text[..start].matches('\n').count() as i64 + 1;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text[start + "\"timeline\"".len()..].chars() {
+        if c == '\n' {
+            line += 1;
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                '[' | '{' => {
+                    depth += 1;
+                    if c == '{' && depth == 2 {
+                        lines.push(line);
+                    }
+                }
+                ']' | '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    lines
+}
+
+struct DapServer {
+    out: tokio::io::Stdout,
+    seq: i64,
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    launch: Option<LaunchArgs>,
+    sources: HashMap<PathBuf, SourceMap>,
+    /// Breakpoint ticks per source file
+    breakpoints: HashMap<PathBuf, BTreeSet<u32>>,
+    child: Option<Child>,
+    child_stdin: Option<ChildStdin>,
+    /// Latest stop reported by the child
+    stop: Option<Value>,
+}
+
+impl DapServer {
+    async fn send(&mut self, mut message: Value) -> Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = serde_json::to_string(&message)?;
+        self.out
+            .write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+            .await?;
+        self.out.flush().await?;
+        Ok(())
+    }
+
+    async fn respond(&mut self, request: &Value, body: Value) -> Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+        .await
+    }
+
+    async fn respond_error(&mut self, request: &Value, message: &str) -> Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+        .await
+    }
+
+    async fn event(&mut self, event: &str, body: Value) -> Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+            .await
+    }
+
+    async fn output(&mut self, category: &str, line: &str) -> Result<()> {
+        self.event(
+            "output",
+            json!({ "category": category, "output": format!("{}\n", line) }),
+        )
+        .await
+    }
+
+    /// Ticks with a breakpoint in any file
+    fn breakpoint_ticks(&self) -> BTreeSet<u32> {
+        self.breakpoints.values().flatten().copied().collect()
+    }
+
+    /// Send a breakpoint console command to the child
+    async fn send_console(&mut self, command: &str) -> Result<()> {
+        let stdin = self
+            .child_stdin
+            .as_mut()
+            .context("The test run has not started")?;
+        stdin.write_all(format!("{}\n", command).as_bytes()).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Returns false once the session should end
+    async fn handle_request(
+        &mut self,
+        request: Value,
+        events: &mpsc::UnboundedSender<Incoming>,
+    ) -> Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default().to_string();
+        let arguments = request["arguments"].clone();
+
+        match command.as_str() {
+            "initialize" => {
+                self.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": false,
+                        "supportsTerminateRequest": true,
+                    }),
+                )
+                .await?;
+            }
+            "launch" => {
+                let launch: LaunchArgs =
+                    serde_json::from_value(arguments).context("Invalid launch arguments")?;
+                let Some(program) = launch.program.clone() else {
+                    self.respond_error(&request, "Launch configuration needs a 'program'")
+                        .await?;
+                    return Ok(true);
+                };
+
+                for test_file in TestLoader::collect_test_files(&program, launch.recursive)? {
+                    match SourceMap::load(&test_file) {
+                        Ok(source) => {
+                            // Editors refer to sources by absolute path
+                            let path = test_file.canonicalize().unwrap_or(test_file);
+                            self.sources.insert(path, source);
+                        }
+                        Err(e) => self.output("stderr", &e.to_string()).await?,
+                    }
+                }
+
+                self.launch = Some(launch);
+                self.respond(&request, json!({})).await?;
+                self.event("initialized", json!({})).await?;
+            }
+            "setBreakpoints" => {
+                let path = PathBuf::from(arguments["source"]["path"].as_str().unwrap_or_default());
+                let lines: Vec<i64> = arguments["breakpoints"]
+                    .as_array()
+                    .map(|breakpoints| {
+                        breakpoints
+                            .iter()
+                            .filter_map(|breakpoint| breakpoint["line"].as_i64())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                let source = SourceMap::load(&path).ok();
+
+                let mut ticks = BTreeSet::new();
+                let mut verified = Vec::new();
+                for line in lines {
+                    match source
+                        .as_ref()
+                        .and_then(|source| source.entry_for_line(line))
+                    {
+                        Some((entry_line, tick)) => {
+                            ticks.insert(tick);
+                            verified.push(json!({
+                                "verified": true,
+                                "line": entry_line,
+                                "message": format!("Breaks at the end of tick {}", tick),
+                            }));
+                        }
+                        None => verified.push(json!({
+                            "verified": false,
+                            "line": line,
+                            "message": "Not a timeline entry",
+                        })),
+                    }
+                }
+
+                // Breakpoints changed while running are queued on the console and take
+                // effect at the next stop. A tick stays a breakpoint while any file has it.
+                let before = self.breakpoint_ticks();
+                self.breakpoints.insert(path, ticks);
+                if self.child_stdin.is_some() {
+                    let after = self.breakpoint_ticks();
+                    for tick in after.difference(&before) {
+                        self.send_console(&format!("break {}", tick)).await?;
+                    }
+                    for tick in before.difference(&after) {
+                        self.send_console(&format!("unbreak {}", tick)).await?;
+                    }
+                }
+
+                self.respond(&request, json!({ "breakpoints": verified }))
+                    .await?;
+            }
+            "setExceptionBreakpoints" => {
+                self.respond(&request, json!({ "breakpoints": [] })).await?;
+            }
+            "configurationDone" => {
+                self.spawn_child(events.clone()).await?;
+                self.respond(&request, json!({})).await?;
+            }
+            "threads" => {
+                self.respond(
+                    &request,
+                    json!({ "threads": [{ "id": THREAD_ID, "name": "Timeline" }] }),
+                )
+                .await?;
+            }
+            "stackTrace" => {
+                let frames = self.stack_frames();
+                let total = frames.len();
+                self.respond(
+                    &request,
+                    json!({ "stackFrames": frames, "totalFrames": total }),
+                )
+                .await?;
+            }
+            "scopes" => {
+                self.respond(
+                    &request,
+                    json!({ "scopes": [
+                        { "name": "Watched blocks", "variablesReference": WATCHES_REFERENCE, "expensive": false },
+                        { "name": "Tests", "variablesReference": TESTS_REFERENCE, "expensive": false },
+                    ]}),
+                )
+                .await?;
+            }
+            "variables" => {
+                let variables = self.variables(arguments["variablesReference"].as_i64());
+                self.respond(&request, json!({ "variables": variables }))
+                    .await?;
+            }
+            "continue" => {
+                self.send_console("c").await?;
+                self.respond(&request, json!({ "allThreadsContinued": true }))
+                    .await?;
+            }
+            "next" | "stepIn" | "stepOut" => {
+                self.send_console("s").await?;
+                self.respond(&request, json!({})).await?;
+            }
+            "pause" => {
+                self.respond_error(
+                    &request,
+                    "Pausing a running timeline is not supported; set a breakpoint instead",
+                )
+                .await?;
+            }
+            "evaluate" => {
+                // Expressions are breakpoint console commands, e.g. "watch 0 100 0" or
+                // "block 1 2 3"; their output arrives as regular output events
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                self.send_console(expression).await?;
+                self.respond(&request, json!({ "result": "", "variablesReference": 0 }))
+                    .await?;
+            }
+            "disconnect" | "terminate" => {
+                if let Some(child) = self.child.as_mut() {
+                    stop_child(child).await;
+                }
+                self.respond(&request, json!({})).await?;
+                // Terminate keeps the session open until the editor disconnects
+                return Ok(command == "terminate");
+            }
+            _ => {
+                self.respond_error(&request, &format!("Unsupported request: {}", command))
+                    .await?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Start `flintmc run` for the launch configuration, with stdin/stdout piped
+    async fn spawn_child(&mut self, events: mpsc::UnboundedSender<Incoming>) -> Result<()> {
+        let launch = self
+            .launch
+            .as_ref()
+            .context("configurationDone before launch")?;
+
+        let mut command = Command::new(std::env::current_exe()?);
+        command.arg("run").arg("--machine-events");
+        if let Some(ref program) = launch.program {
+            command.arg(program);
+        }
+        if let Some(ref server) = launch.server {
+            command.arg("--server").arg(server);
+        }
+        if launch.recursive {
+            command.arg("--recursive");
+        }
+        for tag in &launch.tags {
+            command.arg("--tag").arg(tag);
+        }
+        if launch.stop_on_entry {
+            command.arg("--break-after-setup");
+        }
+        if let Some(ref config_path) = self.config_path {
+            command.arg("--config").arg(config_path);
+        }
+        if let Some(ref profile) = self.profile {
+            command.arg("--profile").arg(profile);
+        }
+        for tick in self.breakpoint_ticks() {
+            command.arg("--break-at").arg(tick.to_string());
+        }
+
+        let mut child = command
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start the test run")?;
+
+        let stdout = child.stdout.take().context("Child has no stdout")?;
+        let stderr = child.stderr.take().context("Child has no stderr")?;
+        self.child_stdin = child.stdin.take();
+        self.child = Some(child);
+
+        let stderr_events = events.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let _ = stderr_events.send(Incoming::Stderr(line));
+            }
+        });
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let _ = events.send(Incoming::Stdout(line));
+            }
+            let _ = events.send(Incoming::ChildExited);
+        });
+
+        Ok(())
+    }
+
+    /// One frame per test still active at the current stop, pointing at its JSON file
+    fn stack_frames(&self) -> Vec<Value> {
+        let Some(ref stop) = self.stop else {
+            return Vec::new();
+        };
+        let next_tick = stop["next_tick"].as_u64().unwrap_or(0) as u32;
+
+        stop["tests"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|test| test["status"] == "active")
+            .filter_map(|test| test["name"].as_str())
+            .enumerate()
+            .map(|(idx, name)| {
+                let source = self
+                    .sources
+                    .iter()
+                    .find(|(_, source)| source.test_name == name);
+                let tick_label = match next_tick {
+                    0 => "setup".to_string(),
+                    tick => format!("tick {}", tick - 1),
+                };
+                let mut frame = json!({
+                    "id": idx + 1,
+                    "name": format!("{} @ {}", name, tick_label),
+                    "line": source.map(|(_, source)| source.line_for_tick(next_tick)).unwrap_or(0),
+                    "column": 1,
+                });
+                if let Some((path, _)) = source {
+                    frame["source"] = json!({
+                        "name": path.file_name().map(|name| name.to_string_lossy().to_string()),
+                        "path": path,
+                    });
+                }
+                frame
+            })
+            .collect()
+    }
+
+    fn variables(&self, reference: Option<i64>) -> Vec<Value> {
+        let Some(ref stop) = self.stop else {
+            return Vec::new();
+        };
+
+        match reference {
+            Some(WATCHES_REFERENCE) => stop["watches"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|watch| {
+                    let pos = &watch["pos"];
+                    json!({
+                        "name": format!("{} [{}, {}, {}]", watch["test"].as_str().unwrap_or_default(), pos[0], pos[1], pos[2]),
+                        "value": watch["block"].as_str().unwrap_or("<unknown>"),
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            Some(TESTS_REFERENCE) => stop["tests"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|test| {
+                    json!({
+                        "name": test["name"],
+                        "value": test["status"],
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Turn a stop line from the child into a DAP `stopped` event
+    async fn handle_stop(&mut self, event: Value) -> Result<()> {
+        let kind = event["kind"].as_str().unwrap_or("step").to_string();
+        self.stop = Some(event);
+
+        // Console commands re-report the stop so variables stay current
+        if kind == "refresh" {
+            return Ok(());
+        }
+
+        self.event(
+            "stopped",
+            json!({
+                "reason": kind,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )
+        .await
+    }
+}
+
+/// Read one `Content-Length` framed message
+async fn read_message<R: tokio::io::AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            content_length = Some(length.trim().parse::<usize>()?);
+        }
+    }
+
+    let length = content_length.context("DAP message without Content-Length")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Serve DAP on stdin/stdout until the editor disconnects
+pub async fn serve(config_path: Option<PathBuf>, profile: Option<String>) -> Result<()> {
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+
+    let request_events = events_tx.clone();
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(tokio::io::stdin());
+        loop {
+            match read_message(&mut reader).await {
+                Ok(Some(request)) => {
+                    let _ = request_events.send(Incoming::Request(request));
+                }
+                Ok(None) | Err(_) => {
+                    let _ = request_events.send(Incoming::ClientClosed);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut server = DapServer {
+        out: tokio::io::stdout(),
+        seq: 0,
+        config_path,
+        profile,
+        launch: None,
+        sources: HashMap::new(),
+        breakpoints: HashMap::new(),
+        child: None,
+        child_stdin: None,
+        stop: None,
+    };
+
+    // Errors end the session too, but never before the run has cleaned up the server
+    let result = async {
+        while let Some(incoming) = events_rx.recv().await {
+            match incoming {
+                Incoming::Request(request) => {
+                    match server.handle_request(request.clone(), &events_tx).await {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(e) => server.respond_error(&request, &e.to_string()).await?,
+                    }
+                }
+                Incoming::Stdout(line) => match line.strip_prefix(STOP_EVENT_PREFIX) {
+                    Some(event) => match serde_json::from_str(event) {
+                        Ok(event) => server.handle_stop(event).await?,
+                        Err(_) => server.output("stdout", &line).await?,
+                    },
+                    None => server.output("stdout", &line).await?,
+                },
+                Incoming::Stderr(line) => server.output("stderr", &line).await?,
+                Incoming::ChildExited => {
+                    let exit_code = match server.child.as_mut() {
+                        Some(child) => child.wait().await?.code().unwrap_or(1),
+                        None => 0,
+                    };
+                    server.child_stdin = None;
+                    server
+                        .event("exited", json!({ "exitCode": exit_code }))
+                        .await?;
+                    server.event("terminated", json!({})).await?;
+                }
+                Incoming::ClientClosed => break,
+            }
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Some(mut child) = server.child.take() {
+        stop_child(&mut child).await;
+    }
+
+    result
+}
+
+/// Interrupt the test run like Ctrl-C, so it unfreezes and clears the server, and only
+/// kill it if it has not exited in time
+async fn stop_child(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        let _ = Command::new("kill")
+            .arg("-INT")
+            .arg(pid.to_string())
+            .status()
+            .await;
+    }
+    if tokio::time::timeout(CHILD_STOP_TIMEOUT, child.wait())
+        .await
+        .is_err()
+    {
+        let _ = child.kill().await;
+    }
+}
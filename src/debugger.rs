@@ -0,0 +1,227 @@
+use flint_core::test_spec::TestSpec;
+use std::collections::HashSet;
+
+/// Prefix of the JSON line printed at every stop when machine events are enabled
+pub const STOP_EVENT_PREFIX: &str = "@flintmc-stop ";
+
+pub const HELP: &[&str] = &[
+    "s, step [N]          advance N ticks (default 1), then break again",
+    "c, continue          run to the next breakpoint (also Enter)",
+    "goto tick T          run until the end of tick T",
+    "block x y z [test]   show the block at local coordinates",
+    "state x y z prop [test]  show a block state property",
+    "run <command>        send a raw server command",
+    "tests                list the tests active at this tick",
+    "skip <test>          skip the remaining actions of a test",
+    "watch x y z [test]   print this block after every step",
+    "unwatch              clear all watches",
+    "break T              also break at the end of tick T",
+    "unbreak T            remove a breakpoint added with break",
+    "help                 show this help",
+];
+
+/// A command typed at a breakpoint, from stdin or in-game chat
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugCommand {
+    Step(u32),
+    Continue,
+    Goto(u32),
+    Block {
+        pos: [i32; 3],
+        test: Option<String>,
+    },
+    State {
+        pos: [i32; 3],
+        property: String,
+        test: Option<String>,
+    },
+    Run(String),
+    Tests,
+    Skip(String),
+    Watch {
+        pos: [i32; 3],
+        test: Option<String>,
+    },
+    Unwatch,
+    Break(u32),
+    Unbreak(u32),
+    Help,
+}
+
+impl DebugCommand {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let mut words = input.split_whitespace();
+        let Some(command) = words.next() else {
+            // Enter continues, like before
+            return Ok(Self::Continue);
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command.to_lowercase().as_str() {
+            "s" | "step" => match args.first() {
+                Some(count) => count
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .map(Self::Step)
+                    .ok_or_else(|| format!("Invalid tick count: {}", count)),
+                None => Ok(Self::Step(1)),
+            },
+            "c" | "continue" => Ok(Self::Continue),
+            "goto" => {
+                let tick = match args.as_slice() {
+                    ["tick", tick] | [tick] => tick,
+                    _ => return Err("Usage: goto tick T".to_string()),
+                };
+                tick.parse()
+                    .map(Self::Goto)
+                    .map_err(|_| format!("Invalid tick: {}", tick))
+            }
+            "block" => {
+                let (pos, rest) = parse_pos(&args, "block x y z [test]")?;
+                Ok(Self::Block {
+                    pos,
+                    test: rest.first().map(|test| test.to_string()),
+                })
+            }
+            "state" => {
+                let (pos, rest) = parse_pos(&args, "state x y z prop [test]")?;
+                let Some(property) = rest.first() else {
+                    return Err("Usage: state x y z prop [test]".to_string());
+                };
+                Ok(Self::State {
+                    pos,
+                    property: property.to_string(),
+                    test: rest.get(1).map(|test| test.to_string()),
+                })
+            }
+            "run" => {
+                let command = input[command.len()..].trim();
+                if command.is_empty() {
+                    Err("Usage: run <command>".to_string())
+                } else {
+                    Ok(Self::Run(command.to_string()))
+                }
+            }
+            "tests" => Ok(Self::Tests),
+            "skip" => match args.first() {
+                Some(test) => Ok(Self::Skip(test.to_string())),
+                None => Err("Usage: skip <test>".to_string()),
+            },
+            "watch" => {
+                let (pos, rest) = parse_pos(&args, "watch x y z [test]")?;
+                Ok(Self::Watch {
+                    pos,
+                    test: rest.first().map(|test| test.to_string()),
+                })
+            }
+            "unwatch" => Ok(Self::Unwatch),
+            "break" => match args.first() {
+                Some(tick) => tick
+                    .parse()
+                    .map(Self::Break)
+                    .map_err(|_| format!("Invalid tick: {}", tick)),
+                None => Err("Usage: break T".to_string()),
+            },
+            "unbreak" => match args.first() {
+                Some(tick) => tick
+                    .parse()
+                    .map(Self::Unbreak)
+                    .map_err(|_| format!("Invalid tick: {}", tick)),
+                None => Err("Usage: unbreak T".to_string()),
+            },
+            "help" | "h" | "?" => Ok(Self::Help),
+            other => Err(format!("Unknown command '{}' (type 'help')", other)),
+        }
+    }
+}
+
+fn parse_pos<'a>(args: &'a [&'a str], usage: &str) -> Result<([i32; 3], &'a [&'a str]), String> {
+    if args.len() < 3 {
+        return Err(format!("Usage: {}", usage));
+    }
+    let mut pos = [0; 3];
+    for (axis, value) in args[..3].iter().enumerate() {
+        pos[axis] = value
+            .parse()
+            .map_err(|_| format!("Invalid coordinate: {}", value))?;
+    }
+    Ok((pos, &args[3..]))
+}
+
+/// A block printed after every step
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub test_idx: usize,
+    pub pos: [i32; 3],
+}
+
+/// Debugger state that lives for the duration of one run
+#[derive(Debug, Default)]
+pub struct DebugSession {
+    /// Tests whose remaining actions are no longer executed
+    pub skipped: HashSet<usize>,
+    pub watches: Vec<Watch>,
+    /// Last tick with an action, per test
+    pub last_tick: Vec<u32>,
+}
+
+impl DebugSession {
+    pub fn new(last_tick: Vec<u32>) -> Self {
+        Self {
+            last_tick,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the test still has actions at or after `tick` and has not been skipped
+    pub fn is_active(&self, test_idx: usize, tick: u32) -> bool {
+        !self.skipped.contains(&test_idx) && self.last_tick[test_idx] >= tick
+    }
+
+    /// Find the test local coordinates refer to: the named test, or the first active one
+    pub fn resolve_test(
+        &self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        name: Option<&str>,
+        tick: u32,
+    ) -> Result<usize, String> {
+        match name {
+            Some(name) => tests_with_offsets
+                .iter()
+                .position(|(test, _)| test.name == name)
+                .ok_or_else(|| format!("No test named {}", name)),
+            None => (0..tests_with_offsets.len())
+                .find(|test_idx| self.is_active(*test_idx, tick))
+                .or_else(|| (!tests_with_offsets.is_empty()).then_some(0))
+                .ok_or_else(|| "No tests are running".to_string()),
+        }
+    }
+}
+
+/// Who typed a debugger command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    /// The terminal running FlintMC, or the editor driving it
+    Terminal,
+    /// In-game chat, with the player's name when the message had one
+    Chat(Option<String>),
+}
+
+/// Player name from the `<player>` prefix of a chat message
+pub fn chat_sender(message: &str) -> Option<&str> {
+    let (name, _) = message.trim().strip_prefix('<')?.split_once("> ")?;
+    Some(name)
+}
+
+/// Strip the `<player>` prefix from a chat message, if present
+pub fn chat_content(message: &str) -> &str {
+    let message = message.trim();
+    if message.starts_with('<')
+        && let Some((_, content)) = message.split_once("> ")
+    {
+        return content.trim();
+    }
+    message
+}
@@ -0,0 +1,86 @@
+//! Errors that tell a failing test apart from a run that could not test anything
+
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Exit code when tests failed
+pub const EXIT_FAILED: i32 = 1;
+
+/// Exit code when the server, bot or specs got in the way of testing
+pub const EXIT_INFRASTRUCTURE: i32 = 2;
+
+/// Exit code when the run was stopped with Ctrl-C
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+#[derive(Debug, Error)]
+pub enum FlintError {
+    /// The bot is not connected or could not connect
+    #[error("Connection failed: {0}")]
+    Connection(String),
+
+    /// The server answered with a command error
+    #[error("Command rejected (last sent: `{command}`): {message}")]
+    CommandRejected { command: String, message: String },
+
+    /// The bot has no chunk data for a world position, so its block is unknown
+    #[error(
+        "Chunk at [{}, {}, {}] is not loaded by the bot (out of its view distance or the world)",
+        .pos[0], .pos[1], .pos[2]
+    )]
+    ChunkNotLoaded { pos: [i32; 3] },
+
+    /// A cleanup region reaches above or below what its dimension can hold
+    #[error(
+        "Cleanup region spans Y {low} to {high}, outside the build height of {dimension} ({min} to {max})"
+    )]
+    OutsideBuildHeight {
+        dimension: String,
+        low: i32,
+        high: i32,
+        min: i32,
+        max: i32,
+    },
+
+    /// A region cannot be saved before a run on an existing map
+    #[error(
+        "Cleanup region is {}x{}x{} blocks, larger than the {max}x{max}x{max} a structure block can back up",
+        .size[0], .size[1], .size[2], max = crate::backup::MAX_BACKUP_SIZE
+    )]
+    BackupTooLarge { size: [i32; 3] },
+
+    #[error("Timed out after {}s waiting for {what}", .after.as_secs())]
+    Timeout { what: String, after: Duration },
+
+    /// Shown without the path, which callers print next to it
+    #[error("{message}")]
+    InvalidSpec { path: PathBuf, message: String },
+
+    /// The world does not look like the test expects
+    #[error("{0}")]
+    AssertionMismatch(String),
+
+    /// A test changed a block outside its cleanup region, `pos` being local to that test
+    #[error("Leaked into {} at [{}, {}, {}] ({block})", leak_target(.victim), .pos[0], .pos[1], .pos[2])]
+    Leak {
+        victim: Option<String>,
+        pos: [i32; 3],
+        block: String,
+    },
+}
+
+fn leak_target(victim: &Option<String>) -> String {
+    match victim {
+        Some(victim) => format!("the region of {}", victim),
+        None => "the world".to_string(),
+    }
+}
+
+/// Anything but an assertion mismatch or leak, including errors without a type, is an
+/// infrastructure error: the test could not be run, so it neither passed nor failed
+pub fn is_infrastructure(error: &anyhow::Error) -> bool {
+    !matches!(
+        error.downcast_ref::<FlintError>(),
+        Some(FlintError::AssertionMismatch(_) | FlintError::Leak { .. })
+    )
+}
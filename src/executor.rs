@@ -1,14 +1,146 @@
-use crate::bot::TestBot;
+use crate::backup;
+use crate::bot::{self, RateLimit, TestBot};
+use crate::debugger::{self, DebugCommand, DebugSession, InputSource, Watch};
+use crate::error::{self, FlintError};
+use crate::interference::LeakDetector;
+use crate::interrupt;
+use crate::snapshot::{self, RegionSnapshot};
+use crate::spec::{ExtensionAction, SpecExtensions};
+use crate::timing::{AssertionTiming, RunTimings, SprintTiming, TestTiming};
+use crate::world::{self, WorldSetup};
 use anyhow::Result;
 use colored::Colorize;
 use flint_core::results::TestResult;
 use flint_core::test_spec::{ActionType, TestSpec, TimelineEntry};
 use flint_core::timeline::TimelineAggregate;
+use std::collections::{BTreeMap, HashSet};
 use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+
+/// Time allowed for the server to answer a command that reads global state
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Game ticks until block updates scheduled before a cleanup have run: lava in the
+/// overworld flows after 30
+const SCHEDULED_UPDATE_TICKS: u64 = 30;
+
+/// What an assertion actually read from the world, recorded for `flintmc compare`
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub test: String,
+    pub tick: u32,
+    /// Local position
+    pub pos: [i32; 3],
+    /// Block state property for `assert_state`, None when the whole block was read
+    pub property: Option<String>,
+    /// None when the bot had no chunk data for the position
+    pub value: Option<String>,
+}
 
 pub struct TestExecutor {
     bot: TestBot,
     use_chat_control: bool,
+    poll_attempts: u32,
+    poll_delay_ms: u64,
+    sprint_timeout: Duration,
+    chunk_timeout: Duration,
+    extra_breakpoints: HashSet<u32>,
+    machine_events: bool,
+    update_snapshots: bool,
+    /// Running on an existing map: regions are saved first and placed back afterwards
+    preserve_world: bool,
+    /// Players allowed to use the debugger's `run` from chat
+    run_players: Vec<String>,
+    observations: Option<Vec<Observation>>,
+    /// Debugger commands typed in the terminal, opened at the first breakpoint
+    debug_input: Option<Lines<BufReader<Stdin>>>,
+    timings: RunTimings,
+    /// What the running wave changed in the world, undone by `teardown` if it stops early
+    wave_state: WaveState,
+    /// Assertions passed and failed, and infrastructure errors, per test of the running wave
+    wave_results: Vec<(usize, usize, usize)>,
+    /// Tests whose last run had an infrastructure error
+    errored_tests: HashSet<String>,
+    interrupted: bool,
+}
+
+/// World changes of a wave that must not outlive it
+#[derive(Default)]
+struct WaveState {
+    frozen: bool,
+    /// Cleanup regions in world coordinates
+    regions: Vec<[[i32; 3]; 2]>,
+    /// Commands that put back the global state a test's `setup.world` changed
+    world_restore: Vec<String>,
+    /// Whether the wave runs on an existing map, where only saved regions may be cleared
+    preserve_world: bool,
+    /// On an existing map, the regions saved and not placed back yet, and the structures they
+    /// were saved as
+    backups: Vec<([[i32; 3]; 2], String)>,
+}
+
+impl WaveState {
+    /// Regions that may be cleared: every one, or on an existing map only those saved
+    fn clearable(&self) -> Vec<[[i32; 3]; 2]> {
+        if self.preserve_world {
+            self.backups.iter().map(|(region, _)| *region).collect()
+        } else {
+            self.regions.clone()
+        }
+    }
+
+    /// Whether the server still has to be cleaned up after this wave
+    fn is_dirty(&self) -> bool {
+        self.frozen || !self.regions.is_empty()
+    }
+
+    /// Every command that puts the server back the way it was before the wave, in order
+    fn cleanup_commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        for region in self.clearable() {
+            commands.extend(clear_commands(region));
+        }
+        for (region, name) in &self.backups {
+            commands.push(backup::restore_command(*region, name));
+        }
+        if self.frozen {
+            commands.push("tick unfreeze".to_string());
+        }
+        commands.extend(self.world_restore.iter().cloned());
+        for region in &self.regions {
+            commands.extend(bot::forceload_commands(*region, "remove"));
+        }
+        commands
+    }
+}
+
+/// A wave that did not get to clean up, because its future was dropped or it panicked, is
+/// cleaned up with commands sent straight to the server
+impl Drop for TestExecutor {
+    fn drop(&mut self) {
+        if !self.wave_state.is_dirty() {
+            return;
+        }
+        let commands = self.wave_state.cleanup_commands();
+        if self.bot.send_unqueued(&commands) {
+            eprintln!(
+                "{} The run stopped without cleaning up, sent {} cleanup command(s)",
+                "!".magenta().bold(),
+                commands.len()
+            );
+        } else {
+            eprintln!(
+                "{} Could not clean up the server, run these commands manually:",
+                "Error:".red().bold()
+            );
+            for command in commands {
+                eprintln!("  /{}", command);
+            }
+        }
+    }
 }
 
 impl Default for TestExecutor {
@@ -16,6 +148,22 @@ impl Default for TestExecutor {
         Self {
             bot: TestBot::new(),
             use_chat_control: false,
+            poll_attempts: 10,
+            poll_delay_ms: 50,
+            sprint_timeout: Duration::from_secs(30),
+            chunk_timeout: Duration::from_secs(10),
+            extra_breakpoints: HashSet::new(),
+            machine_events: false,
+            update_snapshots: false,
+            preserve_world: false,
+            run_players: Vec::new(),
+            observations: None,
+            debug_input: None,
+            timings: RunTimings::default(),
+            wave_state: WaveState::default(),
+            wave_results: Vec::new(),
+            errored_tests: HashSet::new(),
+            interrupted: false,
         }
     }
 }
@@ -29,8 +177,122 @@ impl TestExecutor {
         self.use_chat_control = enabled;
     }
 
-    /// Returns true to continue, false to step to next tick only
-    async fn wait_for_step(&mut self, reason: &str) -> Result<bool> {
+    pub fn set_username(&mut self, username: &str) {
+        self.bot.set_username(username);
+    }
+
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.bot.set_connect_timeout(timeout);
+    }
+
+    pub fn set_rate_limit(&mut self, rate: RateLimit) {
+        self.bot.set_rate_limit(rate);
+    }
+
+    /// Set how many times assertions re-read a block, and how long to wait between reads
+    pub fn set_polling(&mut self, attempts: u32, delay_ms: u64) {
+        self.poll_attempts = attempts.max(1);
+        self.poll_delay_ms = delay_ms;
+    }
+
+    pub fn set_sprint_timeout(&mut self, timeout: Duration) {
+        self.sprint_timeout = timeout;
+    }
+
+    /// Set how long to wait for the bot to receive the chunks of forceloaded test regions
+    pub fn set_chunk_timeout(&mut self, timeout: Duration) {
+        self.chunk_timeout = timeout;
+    }
+
+    /// Break at the end of these ticks in addition to the breakpoints from the test specs
+    pub fn add_breakpoints(&mut self, ticks: &[u32]) {
+        self.extra_breakpoints.extend(ticks);
+    }
+
+    /// Print a machine-readable event line whenever execution stops, for `flintmc dap`
+    pub fn set_machine_events(&mut self, enabled: bool) {
+        self.machine_events = enabled;
+    }
+
+    /// Write `assert_snapshot` results as the new golden snapshots instead of comparing
+    pub fn set_update_snapshots(&mut self, enabled: bool) {
+        self.update_snapshots = enabled;
+    }
+
+    /// Players allowed to use the debugger's `run` from chat
+    pub fn set_run_players(&mut self, players: Vec<String>) {
+        self.run_players = players;
+    }
+
+    /// Save test regions before clearing them and place them back after each wave
+    pub fn set_preserve_world(&mut self, enabled: bool) {
+        self.preserve_world = enabled;
+    }
+
+    /// Record the values every assertion reads, to be collected with `take_observations`
+    pub fn set_record_observations(&mut self, enabled: bool) {
+        self.observations = enabled.then(Vec::new);
+    }
+
+    pub fn take_observations(&mut self) -> Vec<Observation> {
+        self.observations
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Whether a run was stopped with Ctrl-C; later runs should not be started
+    pub fn interrupted(&self) -> bool {
+        self.interrupted || interrupt::is_requested()
+    }
+
+    /// Whether the last run of a test was cut short by an infrastructure error rather than
+    /// failing an assertion
+    pub fn is_errored(&self, test_name: &str) -> bool {
+        self.errored_tests.contains(test_name)
+    }
+
+    /// Timings of every run since the last call
+    pub fn take_timings(&mut self) -> RunTimings {
+        std::mem::take(&mut self.timings)
+    }
+
+    /// Record a value for `compare`. Values come from the first read at a tick, before any
+    /// retries, so what a test expects does not change what is observed.
+    fn observe(
+        &mut self,
+        test: &str,
+        tick: u32,
+        pos: [i32; 3],
+        property: Option<&str>,
+        value: Option<String>,
+    ) {
+        if let Some(observations) = &mut self.observations {
+            observations.push(Observation {
+                test: test.to_string(),
+                tick,
+                pos,
+                property: property.map(str::to_string),
+                value,
+            });
+        }
+    }
+
+    /// Handle debugger commands at a breakpoint until execution is resumed
+    /// Returns the tick at whose end to break again, or None to run to the next breakpoint
+    async fn wait_for_step(
+        &mut self,
+        reason: &str,
+        kind: &str,
+        next_tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        session: &mut DebugSession,
+    ) -> Result<Option<u32>> {
+        if self.machine_events {
+            self.emit_stop_event(kind, next_tick, tests_with_offsets, session)
+                .await?;
+        }
+
         println!(
             "\n{} {} {}",
             "⏸".yellow().bold(),
@@ -40,9 +302,10 @@ impl TestExecutor {
 
         if self.use_chat_control {
             println!(
-                "  Waiting for in-game chat command: {} = step, {} = continue",
+                "  Waiting for in-game chat command: {} = step, {} = continue, {} = all commands",
                 "s".cyan().bold(),
-                "c".cyan().bold()
+                "c".cyan().bold(),
+                "help".cyan().bold()
             );
 
             // Send chat message to inform player
@@ -59,65 +322,338 @@ impl TestExecutor {
             {
                 // Discard old messages
             }
+        } else {
+            println!(
+                "  Commands: {} = step one tick, {} = continue to next breakpoint, {} = all commands",
+                "s".cyan().bold(),
+                "c".cyan().bold(),
+                "help".cyan().bold()
+            );
+        }
+
+        loop {
+            let (input, source) = self.read_debug_input().await?;
+            let command = match DebugCommand::parse(&input) {
+                Ok(command) => command,
+                Err(message) => {
+                    self.debug_reply(&message).await?;
+                    continue;
+                }
+            };
+            // `run` sends anything as the opped bot, so not every player may use it
+            if matches!(command, DebugCommand::Run(_)) && !self.may_run_commands(&source) {
+                self.debug_reply(
+                    "run is only allowed from the terminal and [debugger] run_players",
+                )
+                .await?;
+                continue;
+            }
+
+            match command {
+                DebugCommand::Step(ticks) => return Ok(Some(next_tick + ticks - 1)),
+                DebugCommand::Continue => return Ok(None),
+                DebugCommand::Goto(tick) if tick >= next_tick => return Ok(Some(tick)),
+                DebugCommand::Goto(tick) => {
+                    self.debug_reply(&format!("Tick {} has already run", tick))
+                        .await?;
+                }
+                command => {
+                    self.run_debug_command(command, next_tick, tests_with_offsets, session)
+                        .await?;
+                    if self.machine_events {
+                        self.emit_stop_event("refresh", next_tick, tests_with_offsets, session)
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Print the current stop as a single JSON line prefixed with `STOP_EVENT_PREFIX`
+    async fn emit_stop_event(
+        &self,
+        kind: &str,
+        next_tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        session: &DebugSession,
+    ) -> Result<()> {
+        let tests: Vec<serde_json::Value> = tests_with_offsets
+            .iter()
+            .enumerate()
+            .map(|(test_idx, (test, _))| {
+                let status = if session.skipped.contains(&test_idx) {
+                    "skipped"
+                } else if session.is_active(test_idx, next_tick) {
+                    "active"
+                } else {
+                    "finished"
+                };
+                serde_json::json!({ "name": test.name, "status": status })
+            })
+            .collect();
+
+        let mut watches = Vec::new();
+        for watch in &session.watches {
+            let (test, offset) = &tests_with_offsets[watch.test_idx];
+            let block = self
+                .bot
+                .block_if_loaded(self.apply_offset(watch.pos, *offset))
+                .await?;
+            watches.push(serde_json::json!({
+                "test": test.name,
+                "pos": watch.pos,
+                "block": block,
+            }));
+        }
+
+        let event = serde_json::json!({
+            "kind": kind,
+            "next_tick": next_tick,
+            "tests": tests,
+            "watches": watches,
+        });
+        println!("{}{}", debugger::STOP_EVENT_PREFIX, event);
+        io::stdout().flush()?;
+        Ok(())
+    }
+
+    /// Whether input from a source may send arbitrary server commands
+    fn may_run_commands(&self, source: &InputSource) -> bool {
+        match source {
+            InputSource::Terminal => true,
+            InputSource::Chat(Some(player)) => self.run_players.contains(player),
+            InputSource::Chat(None) => false,
+        }
+    }
 
-            // Now wait for a fresh chat command
+    /// Read the next debugger command from stdin or in-game chat
+    async fn read_debug_input(&mut self) -> Result<(String, InputSource)> {
+        if self.use_chat_control {
             loop {
                 if let Some(message) = self
                     .bot
                     .recv_chat_timeout(std::time::Duration::from_millis(100))
                     .await
                 {
-                    // Skip messages from the bot itself (contains "Waiting for step/continue")
-                    if message.contains("Waiting for step/continue") {
+                    // Skip messages from the bot itself (broadcast as "[name] ...")
+                    let trimmed = message.trim();
+                    if trimmed.contains("Waiting for step/continue") || trimmed.starts_with('[') {
                         continue;
                     }
 
-                    // Look for commands in the message - match exact commands only
-                    let msg_lower = message.to_lowercase();
-                    let trimmed = msg_lower.trim();
-
-                    // Match the message ending with just "s" or "c" (player commands)
-                    if trimmed.ends_with(" s")
-                        || trimmed == "s"
-                        || trimmed.ends_with(" step")
-                        || trimmed == "step"
+                    // Only accept system messages that are valid commands, so join
+                    // messages and the like are not treated as typos
+                    let content = debugger::chat_content(trimmed);
+                    if content.is_empty()
+                        || (!trimmed.starts_with('<') && DebugCommand::parse(content).is_err())
                     {
-                        println!("  {} Received 's' from chat", "→".blue());
-                        return Ok(false); // Step mode
-                    } else if trimmed.ends_with(" c")
-                        || trimmed == "c"
-                        || trimmed.ends_with(" continue")
-                        || trimmed == "continue"
-                    {
-                        println!("  {} Received 'c' from chat", "→".blue());
-                        return Ok(true); // Continue mode
+                        continue;
                     }
+
+                    println!("  {} Received '{}' from chat", "→".blue(), content);
+                    let player = debugger::chat_sender(trimmed).map(str::to_string);
+                    return Ok((content.to_string(), InputSource::Chat(player)));
                 }
             }
         } else {
-            println!(
-                "  Commands: {} = step one tick, {} = continue to next breakpoint",
-                "s".cyan().bold(),
-                "c".cyan().bold()
-            );
-            print!("  > ");
-            io::stdout().flush()?;
+            if !self.machine_events {
+                print!("  > ");
+                io::stdout().flush()?;
+            }
 
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            let cmd = input.trim().to_lowercase();
+            // Read without blocking the runtime, so Ctrl-C still stops the run at a breakpoint.
+            // At the end of input, an empty line continues.
+            let lines = self
+                .debug_input
+                .get_or_insert_with(|| BufReader::new(tokio::io::stdin()).lines());
+            let input = lines.next_line().await?.unwrap_or_default();
+            Ok((input, InputSource::Terminal))
+        }
+    }
 
-            match cmd.as_str() {
-                "s" | "step" => Ok(false), // Step mode: only advance one tick
-                _ => Ok(true),             // Continue mode (default for Enter or "c")
+    /// Print debugger output, echoing it to chat when chat control is enabled
+    async fn debug_reply(&mut self, line: &str) -> Result<()> {
+        println!("  {}", line);
+        if self.use_chat_control {
+            self.bot.send_command(&format!("say {}", line)).await?;
+        }
+        Ok(())
+    }
+
+    async fn run_debug_command(
+        &mut self,
+        command: DebugCommand,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        session: &mut DebugSession,
+    ) -> Result<()> {
+        match command {
+            DebugCommand::Block { pos, test } => {
+                let test_idx = match session.resolve_test(tests_with_offsets, test.as_deref(), tick)
+                {
+                    Ok(test_idx) => test_idx,
+                    Err(message) => return self.debug_reply(&message).await,
+                };
+                let (test, offset) = &tests_with_offsets[test_idx];
+                let block = self
+                    .bot
+                    .block_if_loaded(self.apply_offset(pos, *offset))
+                    .await?;
+                self.debug_reply(&format!(
+                    "[{}] block at [{}, {}, {}] = {}",
+                    test.name,
+                    pos[0],
+                    pos[1],
+                    pos[2],
+                    block.as_deref().unwrap_or("<unloaded>")
+                ))
+                .await?;
+            }
+            DebugCommand::State {
+                pos,
+                property,
+                test,
+            } => {
+                let test_idx = match session.resolve_test(tests_with_offsets, test.as_deref(), tick)
+                {
+                    Ok(test_idx) => test_idx,
+                    Err(message) => return self.debug_reply(&message).await,
+                };
+                let (test, offset) = &tests_with_offsets[test_idx];
+                let value = match self
+                    .bot
+                    .get_block_state_property(self.apply_offset(pos, *offset), &property)
+                    .await
+                {
+                    Ok(value) => value.unwrap_or_else(|| "<none>".to_string()),
+                    Err(e) => format!("<{}>", e),
+                };
+                self.debug_reply(&format!(
+                    "[{}] state {} at [{}, {}, {}] = {}",
+                    test.name, property, pos[0], pos[1], pos[2], value
+                ))
+                .await?;
             }
+            DebugCommand::Run(command) => {
+                self.bot.send_command(&command).await?;
+                self.debug_reply(&format!("Sent: {}", command)).await?;
+            }
+            DebugCommand::Tests => {
+                for (test_idx, (test, offset)) in tests_with_offsets.iter().enumerate() {
+                    let status = if session.skipped.contains(&test_idx) {
+                        "skipped"
+                    } else if session.is_active(test_idx, tick) {
+                        "active"
+                    } else {
+                        "finished"
+                    };
+                    self.debug_reply(&format!(
+                        "{} ({}, offset [{}, {}, {}], last tick {})",
+                        test.name,
+                        status,
+                        offset[0],
+                        offset[1],
+                        offset[2],
+                        session.last_tick[test_idx]
+                    ))
+                    .await?;
+                }
+            }
+            DebugCommand::Skip(name) => {
+                match tests_with_offsets
+                    .iter()
+                    .position(|(test, _)| test.name == name)
+                {
+                    Some(test_idx) => {
+                        session.skipped.insert(test_idx);
+                        self.debug_reply(&format!("Skipping remaining actions of {}", name))
+                            .await?;
+                    }
+                    None => {
+                        self.debug_reply(&format!("No test named {}", name)).await?;
+                    }
+                }
+            }
+            DebugCommand::Watch { pos, test } => {
+                let test_idx = match session.resolve_test(tests_with_offsets, test.as_deref(), tick)
+                {
+                    Ok(test_idx) => test_idx,
+                    Err(message) => return self.debug_reply(&message).await,
+                };
+                session.watches.push(Watch { test_idx, pos });
+                self.debug_reply(&format!(
+                    "Watching [{}, {}, {}] in {}",
+                    pos[0], pos[1], pos[2], tests_with_offsets[test_idx].0.name
+                ))
+                .await?;
+            }
+            DebugCommand::Break(tick) => {
+                self.extra_breakpoints.insert(tick);
+                self.debug_reply(&format!("Breakpoint added at tick {}", tick))
+                    .await?;
+            }
+            DebugCommand::Unbreak(tick) => {
+                let reply = if self.extra_breakpoints.remove(&tick) {
+                    format!("Breakpoint removed at tick {}", tick)
+                } else {
+                    format!("No breakpoint was added at tick {}", tick)
+                };
+                self.debug_reply(&reply).await?;
+            }
+            DebugCommand::Unwatch => {
+                session.watches.clear();
+                self.debug_reply("Cleared all watches").await?;
+            }
+            DebugCommand::Help => {
+                for line in debugger::HELP {
+                    self.debug_reply(line).await?;
+                }
+            }
+            DebugCommand::Step(_) | DebugCommand::Continue | DebugCommand::Goto(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Print every watched block
+    async fn print_watches(
+        &self,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        session: &DebugSession,
+    ) -> Result<()> {
+        for watch in &session.watches {
+            let (test, offset) = &tests_with_offsets[watch.test_idx];
+            let block = self
+                .bot
+                .block_if_loaded(self.apply_offset(watch.pos, *offset))
+                .await?;
+            println!(
+                "    {} Tick {}: watch [{}] [{}, {}, {}] = {}",
+                "◆".cyan(),
+                tick,
+                test.name,
+                watch.pos[0],
+                watch.pos[1],
+                watch.pos[2],
+                block.as_deref().unwrap_or("<unloaded>").dimmed()
+            );
         }
+        Ok(())
     }
 
     fn apply_offset(&self, pos: [i32; 3], offset: [i32; 3]) -> [i32; 3] {
         [pos[0] + offset[0], pos[1] + offset[1], pos[2] + offset[2]]
     }
 
+    /// Cleanup region of a test in world coordinates
+    fn world_region(&self, test: &TestSpec, offset: [i32; 3]) -> [[i32; 3]; 2] {
+        let region = test.cleanup_region();
+        [
+            self.apply_offset(region[0], offset),
+            self.apply_offset(region[1], offset),
+        ]
+    }
+
     /// Poll for a block at the given position with retries
     /// This handles timing issues in CI environments where block updates may take longer
     async fn poll_block_with_retry(
@@ -126,23 +662,21 @@ impl TestExecutor {
         expected_block: &str,
         max_attempts: u32,
         delay_ms: u64,
-    ) -> Result<Option<String>> {
+    ) -> Result<String> {
         let expected_name = expected_block
             .trim_start_matches("minecraft:")
             .to_lowercase()
             .replace("_", "");
 
         for attempt in 0..max_attempts {
-            let block = self.bot.get_block(world_pos).await?;
+            let actual = self.bot.get_block(world_pos).await?;
 
             // Check if the block matches what we expect
-            if let Some(ref actual) = block {
-                let actual_lower = actual.to_lowercase();
-                if actual_lower.contains(&expected_name)
-                    || actual_lower.replace("_", "").contains(&expected_name)
-                {
-                    return Ok(block);
-                }
+            let actual_lower = actual.to_lowercase();
+            if actual_lower.contains(&expected_name)
+                || actual_lower.replace("_", "").contains(&expected_name)
+            {
+                return Ok(actual);
             }
 
             // If not the last attempt, wait before retrying
@@ -181,7 +715,44 @@ impl TestExecutor {
 
     /// Sprint ticks and capture the time taken from server output
     /// Returns the ms per tick from the server's sprint completion message
-    async fn sprint_ticks(&mut self, ticks: u32) -> Result<u64> {
+    async fn sprint_ticks(&mut self, from_tick: u32, ticks: u32) -> Result<u64> {
+        let (completed, mspt) = self.measure_sprint(from_tick, ticks).await?;
+        match (completed, mspt) {
+            (true, Some(ms)) => {
+                let ms_rounded = ms.ceil() as u64;
+                println!(
+                    "    {} Sprint {} ticks completed in {} ms per tick",
+                    "⚡".dimmed(),
+                    ticks,
+                    ms_rounded
+                );
+                // Return total time: ms per tick * number of ticks
+                Ok(ms_rounded * ticks as u64)
+            }
+            (true, None) => {
+                // If we found the message but couldn't parse, use default
+                println!(
+                    "    {} Sprint {} ticks completed (timing not parsed)",
+                    "⚡".dimmed(),
+                    ticks
+                );
+                Ok(200)
+            }
+            (false, _) => {
+                // Timeout - return default
+                println!(
+                    "    {} Sprint {} ticks (no completion message received)",
+                    "⚡".dimmed(),
+                    ticks
+                );
+                Ok(200)
+            }
+        }
+    }
+
+    /// Run `/tick sprint` and wait for the server's completion message
+    /// Returns whether it completed and the ms per tick it reported
+    async fn measure_sprint(&mut self, from_tick: u32, ticks: u32) -> Result<(bool, Option<f64>)> {
         // Clear any pending chat messages
         while self
             .bot
@@ -192,62 +763,534 @@ impl TestExecutor {
             // Discard old messages
         }
 
-        // Send the sprint command
-        self.bot
-            .send_command(&format!("tick sprint {}", ticks))
-            .await?;
-
-        // Wait for the "Sprint completed" message
-        // Server message format: "Sprint completed with X ticks per second, or Y ms per tick"
-        let timeout = std::time::Duration::from_secs(30);
-        let start = std::time::Instant::now();
+        // Send the sprint command
+        let sprint_start = Instant::now();
+        self.bot
+            .send_command(&format!("tick sprint {}", ticks))
+            .await?;
+
+        // Wait for the "Sprint completed" message
+        // Server message format: "Sprint completed with X ticks per second, or Y ms per tick"
+        let timeout = self.sprint_timeout;
+        let mut completed = false;
+        let mut mspt = None;
+
+        while sprint_start.elapsed() < timeout {
+            if let Some(message) = self
+                .bot
+                .recv_chat_timeout(std::time::Duration::from_millis(100))
+                .await
+            {
+                // Look for "Sprint completed" message
+                if message.contains("Sprint completed") {
+                    completed = true;
+                    // Try to extract ms per tick
+                    // Format: "... or X ms per tick"
+                    if let Some(ms_part) = message.split("or ").nth(1)
+                        && let Some(ms_str) = ms_part.split(" ms per tick").next()
+                    {
+                        mspt = ms_str.trim().parse::<f64>().ok();
+                    }
+                    break;
+                }
+            }
+        }
+
+        self.timings.sprints.push(SprintTiming {
+            from_tick,
+            ticks,
+            mspt,
+            wall: sprint_start.elapsed(),
+        });
+        Ok((completed, mspt))
+    }
+
+    /// Apply a test's `setup.world`, first reading back what it changes so it can be restored.
+    ///
+    /// The time is restored with its day count, which `time set` takes as whole days of 24000
+    /// ticks, so the moon phase stays as it was. Weather cannot be queried: it is restored to
+    /// clear, ending any rain or thunder the map had.
+    async fn apply_world_setup(&mut self, setup: &WorldSetup) -> Result<()> {
+        let mut restore = Vec::new();
+        if setup.time.is_some() {
+            let time: u64 = self
+                .query("time query daytime", world::parse_time)
+                .await?
+                .parse()?;
+            let day: u64 = self
+                .query("time query day", world::parse_time)
+                .await?
+                .parse()?;
+            restore.push(format!("time set {}", day * 24000 + time));
+        }
+        if setup.weather.is_some() {
+            restore.push("weather clear".to_string());
+        }
+        for rule in setup.gamerules.keys() {
+            let value = self
+                .query(&format!("gamerule {}", rule), |message| {
+                    world::parse_gamerule(message, rule)
+                })
+                .await?;
+            restore.push(format!("gamerule {} {}", rule, value));
+        }
+        if setup.difficulty.is_some() {
+            let difficulty = self.query("difficulty", world::parse_difficulty).await?;
+            restore.push(format!("difficulty {}", difficulty));
+        }
+
+        self.wave_state.world_restore = restore;
+        for command in setup.commands() {
+            self.bot.send_command(&command).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a command and wait for the first chat reply `parse` understands
+    async fn query(
+        &mut self,
+        command: &str,
+        parse: impl Fn(&str) -> Option<String>,
+    ) -> Result<String> {
+        // Clear any pending chat messages
+        while self
+            .bot
+            .recv_chat_timeout(std::time::Duration::from_millis(10))
+            .await
+            .is_some()
+        {
+            // Discard old messages
+        }
+
+        self.bot.send_command(command).await?;
+        let start = Instant::now();
+        while start.elapsed() < QUERY_TIMEOUT {
+            if let Some(message) = self
+                .bot
+                .recv_chat_timeout(std::time::Duration::from_millis(100))
+                .await
+                && let Some(value) = parse(&message)
+            {
+                return Ok(value);
+            }
+        }
+        Err(FlintError::Timeout {
+            what: format!("a reply to `{}`", command),
+            after: QUERY_TIMEOUT,
+        }
+        .into())
+    }
+
+    /// Run tests in parallel, in as many waves as the bot needs to see every test area
+    ///
+    /// On Ctrl-C the results so far are returned, tests not run yet are interrupted and
+    /// `interrupted` is set.
+    pub async fn run_tests_parallel(
+        &mut self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        extensions: &[SpecExtensions],
+        break_after_setup: bool,
+    ) -> Result<Vec<TestResult>> {
+        let groups = self.observation_groups(tests_with_offsets, extensions);
+        if groups.len() > 1 {
+            println!(
+                "{} Running {} waves: exclusive tests run alone, and the areas of a wave share a dimension and fit the bot's view distance of {} chunks",
+                "→".blue(),
+                groups.len(),
+                self.bot.view_distance()
+            );
+        }
+
+        let mut results = Vec::new();
+        for (group_idx, group) in groups.into_iter().enumerate() {
+            let tests = &tests_with_offsets[group.clone()];
+            if self.interrupted {
+                self.wave_results.clear();
+                results.extend(self.partial_results(tests));
+                continue;
+            }
+            results.extend(
+                self.run_observed_wave(
+                    tests,
+                    &extensions[group],
+                    break_after_setup && group_idx == 0,
+                )
+                .await?,
+            );
+        }
+        Ok(results)
+    }
+
+    /// Split tests into consecutive groups whose areas the bot sees from a single spot in one
+    /// dimension, with every test that needs the server to itself in a group of its own
+    fn observation_groups(
+        &self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        extensions: &[SpecExtensions],
+    ) -> Vec<Range<usize>> {
+        let view_distance = self.bot.view_distance();
+        let mut groups = Vec::new();
+        let mut start = 0;
+        let mut regions = Vec::new();
+        for (test_idx, ((test, offset), spec_extensions)) in
+            tests_with_offsets.iter().zip(extensions).enumerate()
+        {
+            let region = self.world_region(test, *offset);
+            let alone = spec_extensions.needs_isolation();
+            regions.push(region);
+            let moved = spec_extensions.dimension() != extensions[start].dimension();
+            if regions.len() > 1
+                && (alone || moved || !within_view(chunk_bounds(&regions), view_distance))
+            {
+                groups.push(start..test_idx);
+                start = test_idx;
+                regions = vec![region];
+            }
+            if alone {
+                groups.push(test_idx..test_idx + 1);
+                start = test_idx + 1;
+                regions.clear();
+            }
+        }
+        if start < tests_with_offsets.len() {
+            groups.push(start..tests_with_offsets.len());
+        }
+        groups
+    }
+
+    /// Run one wave of tests, unfreezing time and clearing the test areas even if it stops early
+    ///
+    /// On Ctrl-C the results so far are returned and `interrupted` is set.
+    async fn run_observed_wave(
+        &mut self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        extensions: &[SpecExtensions],
+        break_after_setup: bool,
+    ) -> Result<Vec<TestResult>> {
+        let wave = tokio::select! {
+            result = self.run_wave(tests_with_offsets, extensions, break_after_setup) => Some(result),
+            _ = interrupt::requested() => None,
+        };
+
+        match wave {
+            Some(Ok(results)) => Ok(results),
+            Some(Err(e)) => {
+                println!("\n{} Run stopped early: {}", "✗".red().bold(), e);
+                self.teardown().await;
+                Err(e)
+            }
+            None => {
+                println!(
+                    "\n{} Interrupted, cleaning up (Ctrl-C again to exit immediately)...",
+                    "⏹".yellow().bold()
+                );
+                // A second Ctrl-C exits at once, see `interrupt::install`
+                self.teardown().await;
+                self.interrupted = true;
+                Ok(self.partial_results(tests_with_offsets))
+            }
+        }
+    }
+
+    /// Fill the cleanup regions of the stopped wave with air, then unfreeze time
+    ///
+    /// Every step is attempted even when an earlier one fails, so time is unfrozen whenever
+    /// the server still accepts commands; whatever could not be sent is printed to run by hand.
+    async fn teardown(&mut self) {
+        self.bot.track_block_changes(false);
+        let state = std::mem::take(&mut self.wave_state);
+        if !state.is_dirty() {
+            return;
+        }
+
+        let mut unsent = Vec::new();
+        let mut first_error = None;
+        for command in state.cleanup_commands() {
+            if let Err(e) = self.bot.send_command(&command).await {
+                first_error.get_or_insert(e);
+                unsent.push(command);
+            }
+        }
+        if let Some(e) = first_error {
+            eprintln!(
+                "{} Could not clean up the server ({}), run these commands manually:",
+                "Error:".red().bold(),
+                e
+            );
+            for command in unsent {
+                eprintln!("  /{}", command);
+            }
+            return;
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        println!(
+            "{} Time unfrozen and {} test area(s) cleared",
+            "✓".green(),
+            state.regions.len()
+        );
+    }
+
+    /// Add or remove forceloading of every chunk under the wave's regions
+    async fn forceload(&self, action: &str) -> Result<()> {
+        for region in &self.wave_state.regions {
+            for cmd in bot::forceload_commands(*region, action) {
+                self.bot.send_command(&cmd).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait until the bot has received every chunk of the wave's regions
+    ///
+    /// Tests whose chunks do not arrive within the chunk timeout get an infrastructure error.
+    async fn wait_for_chunks(&mut self, tests_with_offsets: &[(TestSpec, [i32; 3])]) -> Result<()> {
+        let regions = self.wave_state.regions.clone();
+        let deadline = Instant::now() + self.chunk_timeout;
+        loop {
+            let mut missing = Vec::new();
+            for (test_idx, region) in regions.iter().enumerate() {
+                if let Some(pos) = self.first_missing_chunk(*region).await? {
+                    missing.push((test_idx, pos));
+                }
+            }
+
+            if missing.is_empty() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                for (test_idx, pos) in missing {
+                    let name = &tests_with_offsets[test_idx].0.name;
+                    self.record_error(test_idx, name, 0, FlintError::ChunkNotLoaded { pos }.into());
+                }
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// A position in the first chunk of a region the bot has no data for
+    async fn first_missing_chunk(&self, region: [[i32; 3]; 2]) -> Result<Option<[i32; 3]>> {
+        let (min, max) = bot::region_chunks(region);
+        let y = region[0][1].min(region[1][1]);
+        for chunk_x in min[0]..=max[0] {
+            for chunk_z in min[1]..=max[1] {
+                let pos = [chunk_x * 16, y, chunk_z * 16];
+                if self.bot.block_if_loaded(pos).await?.is_none() {
+                    return Ok(Some(pos));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Save every region of the wave with a structure block, failing before anything is
+    /// cleared when one cannot be saved
+    async fn back_up_regions(&mut self, dimension: &str) -> Result<()> {
+        println!("{} Saving all test areas...", "→".blue());
+        for (test_idx, region) in self.wave_state.regions.clone().into_iter().enumerate() {
+            backup::check_size(region)?;
+            for pos in backup::helper_positions(region) {
+                if let Some((_, max)) = world::build_height(dimension)
+                    && pos[1] > max
+                {
+                    anyhow::bail!(
+                        "Cannot save the test area below [{}, {}, {}], it is above the build height",
+                        pos[0],
+                        pos[1],
+                        pos[2]
+                    );
+                }
+                let block = self.bot.get_block(pos).await?;
+                if !is_air(&block) {
+                    anyhow::bail!(
+                        "Cannot save the test area below [{}, {}, {}], the structure block needs air there but found {}",
+                        pos[0],
+                        pos[1],
+                        pos[2],
+                        block
+                    );
+                }
+            }
+
+            let name = backup::structure_name(test_idx);
+            for cmd in backup::save_commands(region, &name) {
+                self.bot.send_command(&cmd).await?;
+            }
+            self.wave_state.backups.push((region, name));
+        }
+        Ok(())
+    }
+
+    /// Empty a region given in world coordinates of blocks, fluids and entities other than players
+    async fn clear_region(&self, region: [[i32; 3]; 2]) -> Result<()> {
+        for cmd in clear_commands(region) {
+            self.bot.send_command(&cmd).await?;
+        }
+        Ok(())
+    }
+
+    /// Check through the bot's view of the world that a cleared region contains only air
+    async fn verify_empty(&self, region: [[i32; 3]; 2], offset: [i32; 3]) -> Result<()> {
+        let mut remaining: Vec<[i32; 3]> = Vec::new();
+        for x in region[0][0].min(region[1][0])..=region[0][0].max(region[1][0]) {
+            for y in region[0][1].min(region[1][1])..=region[0][1].max(region[1][1]) {
+                for z in region[0][2].min(region[1][2])..=region[0][2].max(region[1][2]) {
+                    remaining.push([x, y, z]);
+                }
+            }
+        }
+
+        // Block updates from the cleanup may still be on their way
+        let mut first = None;
+        for attempt in 0..self.poll_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_millis(self.poll_delay_ms)).await;
+            }
+            let mut still_remaining = Vec::new();
+            first = None;
+            for pos in remaining {
+                let state = self.bot.get_block(pos).await?;
+                if !is_air(&state) {
+                    first.get_or_insert((pos, state));
+                    still_remaining.push(pos);
+                }
+            }
+            remaining = still_remaining;
+            if remaining.is_empty() {
+                return Ok(());
+            }
+        }
+
+        let (pos, state) = first.unwrap_or_default();
+        anyhow::bail!(
+            "Cleanup region not empty before the test: {} block(s) remain, e.g. [{}, {}, {}] is {}",
+            remaining.len(),
+            pos[0] - offset[0],
+            pos[1] - offset[1],
+            pos[2] - offset[2],
+            state
+        )
+    }
+
+    /// Let the server run until updates scheduled before the cleanup have happened, so
+    /// `verify_empty` sees whatever they bring back, such as fluids flowing in again
+    ///
+    /// Ticks are counted by asking the server for its game time, so a lagging server gets as
+    /// long as it needs. Without replies to `time query`, a fixed wait of the same length at
+    /// 20 ticks per second is all that is left.
+    async fn settle_scheduled_updates(&mut self) {
+        let Ok(start) = self.game_time().await else {
+            tokio::time::sleep(SCHEDULED_UPDATE_TICKS as u32 * Duration::from_millis(50)).await;
+            return;
+        };
+        // A server that stopped ticking is caught by verify_empty instead
+        let deadline = Instant::now() + self.chunk_timeout;
+        while Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            match self.game_time().await {
+                Ok(now) if now < start + SCHEDULED_UPDATE_TICKS => {}
+                _ => return,
+            }
+        }
+    }
+
+    /// Game time as the server reports it now
+    async fn game_time(&mut self) -> Result<u64> {
+        let time = self.query("time query gametime", world::parse_time).await?;
+        Ok(time.parse()?)
+    }
+
+    /// Results of an interrupted wave: every test fails with the assertions counted so far
+    fn partial_results(&mut self, tests_with_offsets: &[(TestSpec, [i32; 3])]) -> Vec<TestResult> {
+        let counts = std::mem::take(&mut self.wave_results);
+        tests_with_offsets
+            .iter()
+            .enumerate()
+            .map(|(idx, (test, _))| {
+                let (passed, failed, _) = counts.get(idx).copied().unwrap_or_default();
+                TestResult::new(test.name.clone()).with_failure_reason(format!(
+                    "Interrupted after {} passed and {} failed assertions",
+                    passed, failed
+                ))
+            })
+            .collect()
+    }
+
+    /// Count an action that returned an error as a failed assertion or an infrastructure error
+    fn record_error(&mut self, test_idx: usize, test_name: &str, tick: u32, e: anyhow::Error) {
+        if error::is_infrastructure(&e) {
+            self.wave_results[test_idx].2 += 1;
+            self.errored_tests.insert(test_name.to_string());
+            println!(
+                "    {} [{}] Tick {}: {}",
+                "!".magenta().bold(),
+                test_name,
+                tick,
+                format!("ERROR {}", e).magenta()
+            );
+        } else {
+            self.wave_results[test_idx].1 += 1;
+            println!(
+                "    {} [{}] Tick {}: {}",
+                "✗".red().bold(),
+                test_name,
+                tick,
+                e.to_string().red()
+            );
+        }
+    }
+
+    /// Send a test's queued commands and fail it if the server rejected any of them
+    async fn check_test_commands(
+        &mut self,
+        test_idx: usize,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) {
+        if let Err(e) = self.bot.check_commands().await {
+            self.record_error(test_idx, &tests_with_offsets[test_idx].0.name, tick, e);
+        }
+    }
 
-        while start.elapsed() < timeout {
-            if let Some(message) = self
-                .bot
-                .recv_chat_timeout(std::time::Duration::from_millis(100))
-                .await
-            {
-                // Look for "Sprint completed" message
-                if message.contains("Sprint completed") {
-                    // Try to extract ms per tick
-                    // Format: "... or X ms per tick"
-                    if let Some(ms_part) = message.split("or ").nth(1)
-                        && let Some(ms_str) = ms_part.split(" ms per tick").next()
-                            && let Ok(ms) = ms_str.trim().parse::<f64>() {
-                                let ms_rounded = ms.ceil() as u64;
-                                println!(
-                                    "    {} Sprint {} ticks completed in {} ms per tick",
-                                    "⚡".dimmed(),
-                                    ticks,
-                                    ms_rounded
-                                );
-                                // Return total time: ms per tick * number of ticks
-                                return Ok(ms_rounded * ticks as u64);
-                            }
-                    // If we found the message but couldn't parse, use default
-                    println!(
-                        "    {} Sprint {} ticks completed (timing not parsed)",
-                        "⚡".dimmed(),
-                        ticks
-                    );
-                    return Ok(200);
-                }
+    /// Fail tests that changed blocks outside their cleanup region since the last check
+    fn check_leaks(
+        &mut self,
+        leaks: &mut LeakDetector,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) {
+        for leak in leaks.check(self.bot.take_block_changes()) {
+            let (test, offset) = &tests_with_offsets[leak.leaker];
+            let victim = leak
+                .victim
+                .map(|victim| tests_with_offsets[victim].0.name.clone());
+            if let Some(victim) = &victim {
+                println!(
+                    "    {} [{}] Tick {}: region entered by a leak from {}",
+                    "!".yellow().bold(),
+                    victim,
+                    tick,
+                    test.name
+                );
             }
+            let error = FlintError::Leak {
+                victim,
+                pos: [
+                    leak.pos[0] - offset[0],
+                    leak.pos[1] - offset[1],
+                    leak.pos[2] - offset[2],
+                ],
+                block: leak.block,
+            };
+            self.record_error(leak.leaker, &test.name, tick, error.into());
         }
-
-        // Timeout - return default
-        println!(
-            "    {} Sprint {} ticks (no completion message received)",
-            "⚡".dimmed(),
-            ticks
-        );
-        Ok(200)
     }
 
-    pub async fn run_tests_parallel(
+    async fn run_wave(
         &mut self,
         tests_with_offsets: &[(TestSpec, [i32; 3])],
+        extensions: &[SpecExtensions],
         break_after_setup: bool,
     ) -> Result<Vec<TestResult>> {
         println!(
@@ -259,17 +1302,39 @@ impl TestExecutor {
         // Build global merged timeline using flint-core
         let aggregate = TimelineAggregate::from_tests(tests_with_offsets);
 
-        println!("  Global timeline: {} ticks", aggregate.max_tick);
+        // FlintMC's own actions run alongside it, after the flint-core actions of each tick
+        let mut extension_timeline: BTreeMap<u32, Vec<(usize, &ExtensionAction)>> = BTreeMap::new();
+        for (test_idx, spec_extensions) in extensions.iter().enumerate() {
+            for entry in &spec_extensions.actions {
+                for tick in &entry.ticks {
+                    extension_timeline
+                        .entry(*tick)
+                        .or_default()
+                        .push((test_idx, &entry.action));
+                }
+            }
+        }
+        let max_tick = extension_timeline
+            .keys()
+            .next_back()
+            .map_or(aggregate.max_tick, |tick| aggregate.max_tick.max(*tick));
+
+        println!("  Global timeline: {} ticks", max_tick);
         println!(
             "  {} unique tick steps with actions",
             aggregate.unique_tick_count()
         );
-        if !aggregate.breakpoints.is_empty() {
-            let mut sorted_breakpoints: Vec<_> = aggregate.breakpoints.iter().collect();
+        if !aggregate.breakpoints.is_empty() || !self.extra_breakpoints.is_empty() {
+            let mut sorted_breakpoints: Vec<_> = aggregate
+                .breakpoints
+                .iter()
+                .chain(&self.extra_breakpoints)
+                .collect();
             sorted_breakpoints.sort();
+            sorted_breakpoints.dedup();
             println!(
                 "  {} breakpoints at ticks: {:?}",
-                aggregate.breakpoints.len(),
+                sorted_breakpoints.len(),
                 sorted_breakpoints
             );
         }
@@ -278,144 +1343,390 @@ impl TestExecutor {
         }
         println!();
 
+        // Track results per test
+        self.wave_results = vec![(0, 0, 0); tests_with_offsets.len()]; // (passed, failed, errors)
+        for (test, _) in tests_with_offsets {
+            self.errored_tests.remove(&test.name);
+        }
+
+        // On an existing map nothing may be cleared unless it was saved, whatever stops the wave
+        self.wave_state.preserve_world = self.preserve_world;
+
+        // Every command of the wave runs in its dimension
+        let setup_start = Instant::now();
+        let dimension = extensions
+            .first()
+            .map_or(world::OVERWORLD, |spec_extensions| {
+                spec_extensions.dimension()
+            });
+        self.bot.set_dimension(dimension).await;
+
+        // Areas beyond the build height are cut to it, so the commands clearing them still work
+        for (test_idx, (test, offset)) in tests_with_offsets.iter().enumerate() {
+            let mut region = self.world_region(test, *offset);
+            if let Some((min, max)) = world::build_height(dimension) {
+                let low = region[0][1].min(region[1][1]);
+                let high = region[0][1].max(region[1][1]);
+                if low < min || high > max {
+                    let error = FlintError::OutsideBuildHeight {
+                        dimension: dimension.to_string(),
+                        low,
+                        high,
+                        min,
+                        max,
+                    };
+                    self.record_error(test_idx, &test.name, 0, error.into());
+                    region[0][1] = low.clamp(min, max);
+                    region[1][1] = high.clamp(min, max);
+                }
+            }
+            self.wave_state.regions.push(region);
+        }
+
+        // Keep every test area loaded and ticking, wherever the bot is
+        self.forceload("add").await?;
+
+        // Watch from where every test area is within view, whatever the server spawned the bot
+        self.bot
+            .observe_from(observation_point(&self.wave_state.regions))
+            .await?;
+        self.wait_for_chunks(tests_with_offsets).await?;
+
+        // On an existing map nothing is cleared before it has been saved
+        if self.preserve_world {
+            self.back_up_regions(dimension).await?;
+        }
+
         // Clean all test areas before starting
         println!("{} Cleaning all test areas...", "→".blue());
-        for (test, offset) in tests_with_offsets.iter() {
-            let region = test.cleanup_region();
-            let world_min = self.apply_offset(region[0], *offset);
-            let world_max = self.apply_offset(region[1], *offset);
-            let cmd = format!(
-                "fill {} {} {} {} {} {} air",
-                world_min[0], world_min[1], world_min[2], world_max[0], world_max[1], world_max[2]
-            );
-            self.bot.send_command(&cmd).await?;
+        for region in self.wave_state.clearable() {
+            self.clear_region(region).await?;
+        }
+        self.settle_scheduled_updates().await;
+
+        // A test starting in a dirty region cannot be trusted
+        for (test_idx, (test, offset)) in tests_with_offsets.iter().enumerate() {
+            if self.errored_tests.contains(&test.name) {
+                continue;
+            }
+            if let Err(e) = self
+                .verify_empty(self.wave_state.regions[test_idx], *offset)
+                .await
+            {
+                self.record_error(test_idx, &test.name, 0, e);
+            }
+        }
+
+        // Global state only ever belongs to a test running alone
+        if let [spec_extensions] = extensions
+            && let Some(setup) = &spec_extensions.world
+        {
+            self.apply_world_setup(setup).await?;
         }
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
 
         // Freeze time globally
+        self.wave_state.frozen = true;
         self.bot.send_command("tick freeze").await?;
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        // Setup errors belong to the wave, not to the first test to send a command
+        self.bot.check_commands().await?;
+        self.timings.setup += setup_start.elapsed();
+
+        // Blocks changing outside the cleanup regions from here on are leaks
+        let mut leaks = LeakDetector::new(self.wave_state.regions.clone());
+        self.bot.track_block_changes(true);
+
+        // Debugger state: tick at whose end to break next, skipped tests and watches
+        let mut last_tick = vec![0; tests_with_offsets.len()];
+        for (tick, entries) in &aggregate.timeline {
+            for (test_idx, _, _) in entries {
+                last_tick[*test_idx] = last_tick[*test_idx].max(*tick);
+            }
+        }
+        for (tick, entries) in &extension_timeline {
+            for (test_idx, _) in entries {
+                last_tick[*test_idx] = last_tick[*test_idx].max(*tick);
+            }
+        }
+        let mut session = DebugSession::new(last_tick);
+        let mut break_at = None;
 
         // Break after setup if requested
-        let mut stepping_mode = false;
         if break_after_setup {
-            let should_continue = self
-                .wait_for_step("After test setup (cleanup complete, time frozen)")
+            break_at = self
+                .wait_for_step(
+                    "After test setup (cleanup complete, time frozen)",
+                    "entry",
+                    0,
+                    tests_with_offsets,
+                    &mut session,
+                )
                 .await?;
-            stepping_mode = !should_continue;
         }
 
-        // Track results per test
-        let mut test_results: Vec<(usize, usize)> = vec![(0, 0); tests_with_offsets.len()]; // (passed, failed)
-
         // Execute merged timeline
+        let timeline_start = Instant::now();
+        let mut test_wall: Vec<Option<Duration>> = vec![None; tests_with_offsets.len()];
         let mut current_tick = 0;
-        while current_tick <= aggregate.max_tick {
+        while current_tick <= max_tick {
+            // Test whose commands the server has not answered yet. They are checked before
+            // another test sends any, so a rejection is blamed on the test that caused it.
+            let mut unchecked = None;
             if let Some(entries) = aggregate.timeline.get(&current_tick) {
                 for (test_idx, entry, value_idx) in entries {
+                    if session.skipped.contains(test_idx) {
+                        continue;
+                    }
+                    if let Some(previous) = unchecked.filter(|previous| previous != test_idx) {
+                        self.check_test_commands(previous, current_tick, tests_with_offsets)
+                            .await;
+                        unchecked = None;
+                    }
                     let (test, offset) = &tests_with_offsets[*test_idx];
+                    for [min, max] in written_regions(&entry.action_type) {
+                        leaks.wrote(
+                            *test_idx,
+                            [
+                                self.apply_offset(min, *offset),
+                                self.apply_offset(max, *offset),
+                            ],
+                        );
+                    }
 
-                    match self
-                        .execute_action(current_tick, entry, *value_idx, *offset)
-                        .await
-                    {
+                    let action_start = Instant::now();
+                    let result = self
+                        .execute_action(&test.name, current_tick, entry, *value_idx, *offset)
+                        .await;
+                    let action = action_name(&entry.action_type);
+                    if action.starts_with("assert") {
+                        self.timings.assertions.push(AssertionTiming {
+                            test: test.name.clone(),
+                            tick: current_tick,
+                            action,
+                            duration: action_start.elapsed(),
+                        });
+                    }
+
+                    match result {
                         Ok(true) => {
-                            test_results[*test_idx].0 += 1; // increment passed
+                            self.wave_results[*test_idx].0 += 1; // increment passed
                         }
                         Ok(false) => {
                             // Non-assertion action
+                            unchecked = Some(*test_idx);
                         }
-                        Err(e) => {
-                            test_results[*test_idx].1 += 1; // increment failed
-                            println!(
-                                "    {} [{}] Tick {}: {}",
-                                "✗".red().bold(),
-                                test.name,
-                                current_tick,
-                                e.to_string().red()
-                            );
-                        }
+                        Err(e) => self.record_error(*test_idx, &test.name, current_tick, e),
+                    }
+                }
+            }
+            if let Some(entries) = extension_timeline.get(&current_tick) {
+                for (test_idx, action) in entries {
+                    if session.skipped.contains(test_idx) {
+                        continue;
+                    }
+                    if let Some(previous) = unchecked.filter(|previous| previous != test_idx) {
+                        self.check_test_commands(previous, current_tick, tests_with_offsets)
+                            .await;
+                        unchecked = None;
+                    }
+                    let (test, offset) = &tests_with_offsets[*test_idx];
+                    if let Some([min, max]) = action.written_region() {
+                        leaks.wrote(
+                            *test_idx,
+                            [
+                                self.apply_offset(min, *offset),
+                                self.apply_offset(max, *offset),
+                            ],
+                        );
+                    }
+
+                    let action_start = Instant::now();
+                    let result = if matches!(action, ExtensionAction::AssertMspt { .. })
+                        && tests_with_offsets.len() > 1
+                    {
+                        // Tick time is server-wide, so other tests would skew it
+                        Err(anyhow::anyhow!(
+                            "assert_mspt only runs in an isolated wave, not alongside {} other test(s)",
+                            tests_with_offsets.len() - 1
+                        ))
+                    } else {
+                        self.execute_extension(
+                            &test.name,
+                            current_tick,
+                            action,
+                            &extensions[*test_idx],
+                            *offset,
+                        )
+                        .await
+                    };
+                    if action.name().starts_with("assert") {
+                        self.timings.assertions.push(AssertionTiming {
+                            test: test.name.clone(),
+                            tick: current_tick,
+                            action: action.name(),
+                            duration: action_start.elapsed(),
+                        });
                     }
+
+                    match result {
+                        Ok(true) => self.wave_results[*test_idx].0 += 1,
+                        Ok(false) => unchecked = Some(*test_idx),
+                        Err(e) => self.record_error(*test_idx, &test.name, current_tick, e),
+                    }
+                }
+            }
+            if let Some(previous) = unchecked {
+                self.check_test_commands(previous, current_tick, tests_with_offsets)
+                    .await;
+            }
+
+            // Placements of this tick must reach the server before it steps
+            self.bot.flush_commands().await?;
+
+            for (test_idx, last_tick) in session.last_tick.iter().enumerate() {
+                if *last_tick == current_tick {
+                    test_wall[test_idx] = Some(timeline_start.elapsed());
                 }
             }
 
             // Check for breakpoint at end of this tick (before stepping)
-            // Or if we're in stepping mode, break at every tick
-            if aggregate.breakpoints.contains(&current_tick) || stepping_mode {
-                let should_continue = self
-                    .wait_for_step(&format!(
-                        "End of tick {} (before step to next tick)",
-                        current_tick
-                    ))
+            // Or if a step/goto from the debugger ends here
+            let at_breakpoint = aggregate.breakpoints.contains(&current_tick)
+                || self.extra_breakpoints.contains(&current_tick);
+            if at_breakpoint || break_at.is_some_and(|tick| current_tick >= tick) {
+                break_at = self
+                    .wait_for_step(
+                        &format!("End of tick {} (before step to next tick)", current_tick),
+                        if at_breakpoint { "breakpoint" } else { "step" },
+                        current_tick + 1,
+                        tests_with_offsets,
+                        &mut session,
+                    )
                     .await?;
-                stepping_mode = !should_continue;
             }
 
-            // Advance to next tick (step or sprint depending on mode)
-            if current_tick < aggregate.max_tick {
-                if stepping_mode {
-                    // In stepping mode, only advance one tick at a time
-                    self.sprint_ticks(1).await?;
-                    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-                    current_tick += 1;
-                } else {
-                    // In continue mode, sprint to next event or breakpoint
-                    // Use the aggregate's helper method to find the next event
-                    let next_event_tick = aggregate
-                        .next_event_tick(current_tick)
-                        .unwrap_or(aggregate.max_tick + 1);
-
-                    // Calculate how many ticks to sprint
-                    let ticks_to_sprint = if next_event_tick <= aggregate.max_tick {
-                        next_event_tick - current_tick
-                    } else {
-                        aggregate.max_tick - current_tick
-                    };
+            // Advance to next tick, sprinting to the next event, breakpoint or debugger stop
+            if current_tick < max_tick {
+                // Use the aggregate's helper method to find the next event
+                let mut target_tick = aggregate
+                    .next_event_tick(current_tick)
+                    .unwrap_or(max_tick)
+                    .min(max_tick);
+                if let Some(tick) = extension_timeline
+                    .range(current_tick + 1..)
+                    .next()
+                    .map(|(tick, _)| *tick)
+                {
+                    target_tick = target_tick.min(tick);
+                }
+                if let Some(tick) = break_at {
+                    target_tick = target_tick.min(tick);
+                }
+                if let Some(tick) = self
+                    .extra_breakpoints
+                    .iter()
+                    .filter(|tick| **tick > current_tick)
+                    .min()
+                {
+                    target_tick = target_tick.min(*tick);
+                }
 
-                    // Sprint the ticks
-                    let sprint_time_ms = if ticks_to_sprint > 0 {
-                        self.sprint_ticks(ticks_to_sprint).await?
-                    } else {
-                        0
-                    };
+                // Calculate how many ticks to sprint
+                let ticks_to_sprint = target_tick.saturating_sub(current_tick).max(1);
+
+                // Sprint the ticks
+                let sprint_time_ms = self.sprint_ticks(current_tick, ticks_to_sprint).await?;
+
+                // Use sprint timing for retry delay (ensure at least 200ms)
+                let retry_delay = sprint_time_ms.max(200);
+                tokio::time::sleep(tokio::time::Duration::from_millis(retry_delay)).await;
 
-                    // Use sprint timing for retry delay (ensure at least 200ms)
-                    let retry_delay = sprint_time_ms.max(200);
-                    tokio::time::sleep(tokio::time::Duration::from_millis(retry_delay)).await;
+                current_tick += ticks_to_sprint;
+                self.check_leaks(&mut leaks, current_tick, tests_with_offsets);
 
-                    current_tick += ticks_to_sprint;
+                if !session.watches.is_empty() {
+                    self.print_watches(current_tick, tests_with_offsets, &session)
+                        .await?;
                 }
             } else {
                 current_tick += 1;
             }
         }
 
-        // Unfreeze time
-        self.bot.send_command("tick unfreeze").await?;
+        self.check_leaks(&mut leaks, max_tick, tests_with_offsets);
+        self.bot.track_block_changes(false);
+
+        let timeline_wall = timeline_start.elapsed();
+        for ((test, _), wall) in tests_with_offsets.iter().zip(test_wall) {
+            self.timings.tests.push(TestTiming {
+                test: test.name.clone(),
+                wall: wall.unwrap_or(timeline_wall),
+            });
+        }
 
-        // Clean all test areas after completion
+        // Clean all test areas after completion, still frozen, so updates the tests scheduled
+        // find their blocks gone when time runs again
+        let cleanup_start = Instant::now();
         println!("\n{} Cleaning up all test areas...", "→".blue());
-        for (test, offset) in tests_with_offsets.iter() {
-            let region = test.cleanup_region();
-            let world_min = self.apply_offset(region[0], *offset);
-            let world_max = self.apply_offset(region[1], *offset);
-            let cmd = format!(
-                "fill {} {} {} {} {} {} air",
-                world_min[0], world_min[1], world_min[2], world_max[0], world_max[1], world_max[2]
-            );
-            self.bot.send_command(&cmd).await?;
+        for region in self.wave_state.clearable() {
+            self.clear_region(region).await?;
+        }
+        // A region leaves the backups only once placed back, so a teardown never clears it again
+        while let Some((region, name)) = self.wave_state.backups.last() {
+            self.bot
+                .send_command(&backup::restore_command(*region, name))
+                .await?;
+            self.wave_state.backups.pop();
+        }
+
+        // Unfreeze time
+        self.bot.send_command("tick unfreeze").await?;
+        self.wave_state.frozen = false;
+        for command in std::mem::take(&mut self.wave_state.world_restore) {
+            self.bot.send_command(&command).await?;
+        }
+        self.forceload("remove").await?;
+        // Reported here rather than failing the setup of the next wave
+        if let Err(e) = self.bot.check_commands().await {
+            eprintln!("{} Cleaning up: {}", "Error:".red().bold(), e);
         }
         tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        self.timings.cleanup += cleanup_start.elapsed();
+        self.wave_state.regions.clear();
 
         // Build results
+        let test_results = std::mem::take(&mut self.wave_results);
         let results: Vec<TestResult> = tests_with_offsets
             .iter()
             .enumerate()
             .map(|(idx, (test, _))| {
-                let (passed, failed) = test_results[idx];
-                let success = failed == 0;
+                let (passed, failed, errors) = test_results[idx];
+                let success = failed == 0 && errors == 0;
 
                 println!();
+                if session.skipped.contains(&idx) {
+                    println!(
+                        "  {} [{}] Test skipped from the debugger: {} passed, {} failed",
+                        "⏭".yellow().bold(),
+                        test.name,
+                        passed,
+                        failed
+                    );
+                    return TestResult::new(test.name.clone())
+                        .with_failure_reason("Skipped from the debugger".to_string());
+                }
+                if errors > 0 {
+                    println!(
+                        "  {} [{}] Test errored: {} infrastructure error(s), {} passed, {} failed",
+                        "!".magenta().bold(),
+                        test.name,
+                        errors,
+                        passed,
+                        failed
+                    );
+                    return TestResult::new(test.name.clone())
+                        .with_failure_reason(format!("{} infrastructure error(s)", errors));
+                }
                 if success {
                     println!(
                         "  {} [{}] Test passed: {} assertions",
@@ -470,6 +1781,7 @@ impl TestExecutor {
 
     async fn execute_action(
         &mut self,
+        test_name: &str,
         tick: u32,
         entry: &TimelineEntry,
         value_idx: usize,
@@ -478,11 +1790,7 @@ impl TestExecutor {
         match &entry.action_type {
             ActionType::Place { pos, block } => {
                 let world_pos = self.apply_offset(*pos, offset);
-                let cmd = format!(
-                    "setblock {} {} {} {}",
-                    world_pos[0], world_pos[1], world_pos[2], block
-                );
-                self.bot.send_command(&cmd).await?;
+                self.bot.queue_setblock(world_pos, block).await?;
                 println!(
                     "    {} Tick {}: place at [{}, {}, {}] = {}",
                     "→".blue(),
@@ -498,11 +1806,7 @@ impl TestExecutor {
             ActionType::PlaceEach { blocks } => {
                 for placement in blocks {
                     let world_pos = self.apply_offset(placement.pos, offset);
-                    let cmd = format!(
-                        "setblock {} {} {} {}",
-                        world_pos[0], world_pos[1], world_pos[2], placement.block
-                    );
-                    self.bot.send_command(&cmd).await?;
+                    self.bot.queue_setblock(world_pos, &placement.block).await?;
                     println!(
                         "    {} Tick {}: place at [{}, {}, {}] = {}",
                         "→".blue(),
@@ -512,7 +1816,6 @@ impl TestExecutor {
                         placement.pos[2],
                         placement.block.dimmed()
                     );
-                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
                 }
                 Ok(false)
             }
@@ -548,11 +1851,7 @@ impl TestExecutor {
 
             ActionType::Remove { pos } => {
                 let world_pos = self.apply_offset(*pos, offset);
-                let cmd = format!(
-                    "setblock {} {} {} air",
-                    world_pos[0], world_pos[1], world_pos[2]
-                );
-                self.bot.send_command(&cmd).await?;
+                self.bot.queue_setblock(world_pos, "air").await?;
                 println!(
                     "    {} Tick {}: remove at [{}, {}, {}]",
                     "→".blue(),
@@ -567,22 +1866,27 @@ impl TestExecutor {
             ActionType::Assert { checks } => {
                 for check in checks {
                     let world_pos = self.apply_offset(check.pos, offset);
+                    if self.observations.is_some() {
+                        let observed = self.bot.block_if_loaded(world_pos).await?;
+                        self.observe(test_name, tick, check.pos, None, observed);
+                    }
 
-                    // Poll with retries: 10 attempts, 50ms apart = up to 500ms total
+                    // Poll with retries (10 attempts, 50ms apart by default)
                     // This handles timing issues in CI environments
                     let actual_block = self
-                        .poll_block_with_retry(world_pos, &check.is, 10, 50)
+                        .poll_block_with_retry(
+                            world_pos,
+                            &check.is,
+                            self.poll_attempts,
+                            self.poll_delay_ms,
+                        )
                         .await?;
 
                     let expected_name = check.is.trim_start_matches("minecraft:");
-                    let success = if let Some(ref actual) = actual_block {
-                        let actual_lower = actual.to_lowercase();
-                        let expected_lower = expected_name.to_lowercase().replace("_", "");
-                        actual_lower.contains(&expected_lower)
-                            || actual_lower.replace("_", "").contains(&expected_lower)
-                    } else {
-                        false
-                    };
+                    let actual_lower = actual_block.to_lowercase();
+                    let expected_lower = expected_name.to_lowercase().replace("_", "");
+                    let success = actual_lower.contains(&expected_lower)
+                        || actual_lower.replace("_", "").contains(&expected_lower);
 
                     if success {
                         println!(
@@ -595,14 +1899,11 @@ impl TestExecutor {
                             check.is.dimmed()
                         );
                     } else {
-                        anyhow::bail!(
-                            "Block at [{}, {}, {}] is not {} (got {:?})",
-                            check.pos[0],
-                            check.pos[1],
-                            check.pos[2],
-                            check.is,
-                            actual_block
-                        );
+                        return Err(FlintError::AssertionMismatch(format!(
+                            "Block at [{}, {}, {}] is not {} (got {})",
+                            check.pos[0], check.pos[1], check.pos[2], check.is, actual_block
+                        ))
+                        .into());
                     }
                 }
                 Ok(true)
@@ -611,11 +1912,20 @@ impl TestExecutor {
             ActionType::AssertState { pos, state, values } => {
                 let world_pos = self.apply_offset(*pos, offset);
                 let expected_value = &values[value_idx];
+                if self.observations.is_some() {
+                    let observed = self.bot.get_block_state_property(world_pos, state).await?;
+                    self.observe(test_name, tick, *pos, Some(state.as_str()), observed);
+                }
 
-                // Poll with retries: 10 attempts, 50ms apart = up to 500ms total
+                // Poll with retries (10 attempts, 50ms apart by default)
                 // This handles timing issues in CI environments
                 let actual_value = self
-                    .poll_block_state_with_retry(world_pos, state, 10, 50)
+                    .poll_block_state_with_retry(
+                        world_pos,
+                        state,
+                        self.poll_attempts,
+                        self.poll_delay_ms,
+                    )
                     .await?;
 
                 let success = if let Some(ref actual) = actual_value {
@@ -637,17 +1947,307 @@ impl TestExecutor {
                     );
                     Ok(true)
                 } else {
-                    anyhow::bail!(
+                    Err(FlintError::AssertionMismatch(format!(
                         "Block at [{}, {}, {}] state {} is not {} (got {:?})",
+                        pos[0], pos[1], pos[2], state, expected_value, actual_value
+                    ))
+                    .into())
+                }
+            }
+        }
+    }
+
+    /// Execute a FlintMC extension action, returning whether it was an assertion
+    async fn execute_extension(
+        &mut self,
+        test_name: &str,
+        tick: u32,
+        action: &ExtensionAction,
+        extensions: &SpecExtensions,
+        offset: [i32; 3],
+    ) -> Result<bool> {
+        match action {
+            ExtensionAction::PlaceStructure {
+                file,
+                template,
+                pos,
+                rotation,
+                mirror,
+                structure,
+            } => {
+                let world_pos = self.apply_offset(*pos, offset);
+                if let Some(template) = template {
+                    // The server has the structure, which also keeps block entity data
+                    let cmd = format!(
+                        "place template {} {} {} {} {} {}",
+                        template,
+                        world_pos[0],
+                        world_pos[1],
+                        world_pos[2],
+                        rotation.as_str(),
+                        mirror.as_str()
+                    );
+                    self.bot.send_command(&cmd).await?;
+                    println!(
+                        "    {} Tick {}: place template {} at [{}, {}, {}]",
+                        "→".blue(),
+                        tick,
+                        template.dimmed(),
+                        pos[0],
+                        pos[1],
+                        pos[2]
+                    );
+                } else if let Some(structure) = structure {
+                    // Grouped by block so the command queue can merge them into fills
+                    let mut blocks = structure.placed(*rotation, *mirror);
+                    blocks.sort_by(|a, b| a.1.cmp(&b.1));
+                    for (block_pos, block) in &blocks {
+                        let world_pos = self.apply_offset(*block_pos, world_pos);
+                        self.bot.queue_setblock(world_pos, block).await?;
+                    }
+                    println!(
+                        "    {} Tick {}: place structure {} at [{}, {}, {}] ({} blocks)",
+                        "→".blue(),
+                        tick,
+                        file.as_deref()
+                            .unwrap_or(Path::new(""))
+                            .display()
+                            .to_string()
+                            .dimmed(),
                         pos[0],
                         pos[1],
                         pos[2],
-                        state,
-                        expected_value,
-                        actual_value
+                        blocks.len()
+                    );
+                }
+                Ok(false)
+            }
+
+            ExtensionAction::AssertSnapshot { region, name } => {
+                let name = name.clone().unwrap_or_else(|| format!("tick_{}", tick));
+                let path = snapshot::snapshot_path(&extensions.source, &name);
+                let expected = RegionSnapshot::load(&path)?;
+
+                // Re-read the region until it matches, like single block assertions
+                let mut actual = self.read_region(*region, offset).await?;
+                for pos in RegionSnapshot::positions(*region) {
+                    let value = actual.blocks.get(&RegionSnapshot::key(pos)).cloned();
+                    self.observe(test_name, tick, pos, None, value);
+                }
+                if let Some(expected) = expected.as_ref().filter(|_| !self.update_snapshots) {
+                    for _ in 1..self.poll_attempts {
+                        if expected.diff(&actual).is_empty() {
+                            break;
+                        }
+                        tokio::time::sleep(Duration::from_millis(self.poll_delay_ms)).await;
+                        actual = self.read_region(*region, offset).await?;
+                    }
+                }
+
+                let Some(expected) = expected.filter(|_| !self.update_snapshots) else {
+                    if !self.update_snapshots {
+                        return Err(FlintError::InvalidSpec {
+                            path: extensions.source.clone(),
+                            message: format!(
+                                "snapshot {} does not exist (run with --update-snapshots to create it)",
+                                path.display()
+                            ),
+                        }
+                        .into());
+                    }
+                    actual.save(&path)?;
+                    println!(
+                        "    {} Tick {}: wrote snapshot {} ({} blocks)",
+                        "✓".green(),
+                        tick,
+                        path.display().to_string().dimmed(),
+                        actual.blocks.len()
+                    );
+                    return Ok(true);
+                };
+
+                let diffs = expected.diff(&actual);
+                if diffs.is_empty() {
+                    println!(
+                        "    {} Tick {}: assert snapshot {} ({} blocks)",
+                        "✓".green(),
+                        tick,
+                        name.dimmed(),
+                        actual.blocks.len()
                     );
+                    return Ok(true);
+                }
+
+                const MAX_DIFF_LINES: usize = 20;
+                let mut message = format!(
+                    "Region differs from snapshot {} at {} position(s):",
+                    name,
+                    diffs.len()
+                );
+                for diff in diffs.iter().take(MAX_DIFF_LINES) {
+                    message.push_str(&format!(
+                        "\n        [{}] expected {}, got {}",
+                        diff.pos,
+                        diff.expected.as_deref().unwrap_or("nothing"),
+                        diff.actual.as_deref().unwrap_or("nothing")
+                    ));
+                }
+                if diffs.len() > MAX_DIFF_LINES {
+                    message.push_str(&format!(
+                        "\n        ... and {} more",
+                        diffs.len() - MAX_DIFF_LINES
+                    ));
+                }
+                Err(FlintError::AssertionMismatch(message).into())
+            }
+
+            ExtensionAction::AssertMspt { ticks, max } => {
+                // Everything placed this tick has to be in the world while it is measured
+                self.bot.flush_commands().await?;
+                let (completed, mspt) = self.measure_sprint(tick, *ticks).await?;
+                if !completed {
+                    return Err(FlintError::Timeout {
+                        what: format!("a {} tick sprint to complete", ticks),
+                        after: self.sprint_timeout,
+                    }
+                    .into());
+                }
+                let Some(mspt) = mspt else {
+                    anyhow::bail!("No ms per tick in the sprint completion message");
+                };
+                if mspt > *max {
+                    return Err(FlintError::AssertionMismatch(format!(
+                        "{:.2} ms per tick over {} ticks exceeds the budget of {} ms",
+                        mspt, ticks, max
+                    ))
+                    .into());
                 }
+                println!(
+                    "    {} Tick {}: assert mspt {:.2} <= {} over {} ticks",
+                    "✓".green(),
+                    tick,
+                    mspt,
+                    max,
+                    ticks
+                );
+                Ok(true)
             }
         }
     }
+
+    /// Read every block of a local region as a snapshot
+    async fn read_region(&self, region: [[i32; 3]; 2], offset: [i32; 3]) -> Result<RegionSnapshot> {
+        let mut blocks = BTreeMap::new();
+        for pos in RegionSnapshot::positions(region) {
+            let state = self.bot.get_block(self.apply_offset(pos, offset)).await?;
+            blocks.insert(RegionSnapshot::key(pos), state);
+        }
+        Ok(RegionSnapshot { region, blocks })
+    }
+}
+
+/// Commands that empty a region of blocks and entities
+fn clear_commands(region: [[i32; 3]; 2]) -> Vec<String> {
+    // Barriers first: fluids and falling blocks are removed without flowing or dropping
+    let mut commands = bot::region_fill_commands(region, "barrier");
+
+    let min: [i32; 3] = std::array::from_fn(|axis| region[0][axis].min(region[1][axis]));
+    let max: [i32; 3] = std::array::from_fn(|axis| region[0][axis].max(region[1][axis]));
+    commands.push(format!(
+        "kill @e[type=!player,x={},y={},z={},dx={},dy={},dz={}]",
+        min[0],
+        min[1],
+        min[2],
+        max[0] - min[0],
+        max[1] - min[1],
+        max[2] - min[2]
+    ));
+
+    commands.extend(bot::region_fill_commands(region, "air"));
+    commands
+}
+
+/// First and last chunk, as `[x, z]`, covering every region
+fn chunk_bounds(regions: &[[[i32; 3]; 2]]) -> ([i32; 2], [i32; 2]) {
+    let mut chunks = regions.iter().map(|region| bot::region_chunks(*region));
+    let first = chunks.next().unwrap_or_default();
+    chunks.fold(first, |(min, max), (region_min, region_max)| {
+        (
+            [min[0].min(region_min[0]), min[1].min(region_min[1])],
+            [max[0].max(region_max[0]), max[1].max(region_max[1])],
+        )
+    })
+}
+
+/// Chunk in the middle of chunk bounds
+fn centre_chunk((min, max): ([i32; 2], [i32; 2])) -> [i32; 2] {
+    [
+        (min[0] + max[0] + 1).div_euclid(2),
+        (min[1] + max[1] + 1).div_euclid(2),
+    ]
+}
+
+/// Position above the centre chunk of the regions, halfway up them
+fn observation_point(regions: &[[[i32; 3]; 2]]) -> [i32; 3] {
+    let centre = centre_chunk(chunk_bounds(regions));
+    let floor = regions
+        .iter()
+        .map(|region| region[0][1].min(region[1][1]))
+        .min();
+    let ceiling = regions
+        .iter()
+        .map(|region| region[0][1].max(region[1][1]))
+        .max();
+    [
+        centre[0] * 16 + 8,
+        (floor.unwrap_or(0) + ceiling.unwrap_or(0)) / 2,
+        centre[1] * 16 + 8,
+    ]
+}
+
+/// Whether the server sends every chunk within bounds to a bot in the middle one. The area
+/// it sends is round, and its edge is kept a chunk away.
+fn within_view(bounds: ([i32; 2], [i32; 2]), view_distance: u32) -> bool {
+    let (min, max) = bounds;
+    let centre = centre_chunk(bounds);
+    let dx = (centre[0] - min[0]).max(max[0] - centre[0]);
+    let dz = (centre[1] - min[1]).max(max[1] - centre[1]);
+    let reach = view_distance.saturating_sub(1) as i32;
+    dx * dx + dz * dz <= reach * reach
+}
+
+/// Regions, in test-local coordinates, that an action writes blocks to
+fn written_regions(action: &ActionType) -> Vec<[[i32; 3]; 2]> {
+    match action {
+        ActionType::Place { pos, .. } | ActionType::Remove { pos } => vec![[*pos, *pos]],
+        ActionType::PlaceEach { blocks } => blocks
+            .iter()
+            .map(|placement| [placement.pos, placement.pos])
+            .collect(),
+        ActionType::Fill { region, .. } => vec![*region],
+        ActionType::Assert { .. } | ActionType::AssertState { .. } => Vec::new(),
+    }
+}
+
+/// Name of a timeline action as written in test specs
+fn action_name(action: &ActionType) -> &'static str {
+    match action {
+        ActionType::Place { .. } => "place",
+        ActionType::PlaceEach { .. } => "place_each",
+        ActionType::Fill { .. } => "fill",
+        ActionType::Remove { .. } => "remove",
+        ActionType::Assert { .. } => "assert",
+        ActionType::AssertState { .. } => "assert_state",
+    }
+}
+
+/// Whether azalea's debug representation of a block state, e.g. `BlockState(id: 0, Air)`,
+/// is one of the air blocks
+fn is_air(state: &str) -> bool {
+    let name = state
+        .rsplit(", ")
+        .next()
+        .unwrap_or(state)
+        .trim_end_matches(')');
+    matches!(name, "Air" | "CaveAir" | "VoidAir")
 }
@@ -0,0 +1,150 @@
+use crate::spec::{self, SpecExtensions};
+use colored::Colorize;
+use flint_core::test_spec::{ActionType, TestSpec};
+use flint_core::timeline::TimelineAggregate;
+use std::path::PathBuf;
+
+/// Print every discovered test with its tags, length, region size and dependencies
+pub fn list_tests(test_files: &[PathBuf]) -> usize {
+    let mut errors = 0;
+
+    for test_file in test_files {
+        let (entry, extensions) = match spec::load(test_file) {
+            Ok((test, extensions)) => ((test, [0, 0, 0]), extensions),
+            Err(e) => {
+                errors += 1;
+                eprintln!(
+                    "  {} {}: {}",
+                    "✗".red().bold(),
+                    test_file.display(),
+                    e.to_string().red()
+                );
+                continue;
+            }
+        };
+
+        let ticks = TimelineAggregate::from_tests(std::slice::from_ref(&entry))
+            .max_tick
+            .max(extensions.max_tick());
+        let test = &entry.0;
+        let size = region_size(test.cleanup_region());
+
+        println!(
+            "  {} {} {}",
+            "•".blue(),
+            test.name.bold(),
+            test_file.display().to_string().dimmed()
+        );
+        if !test.tags.is_empty() {
+            println!("      tags: {}", test.tags.join(", ").cyan());
+        }
+        println!(
+            "      {} ticks, region {}x{}x{}",
+            ticks, size[0], size[1], size[2]
+        );
+        if !test.dependencies.is_empty() {
+            println!("      depends on: {}", test.dependencies.join(", "));
+        }
+    }
+
+    errors
+}
+
+/// Parse every spec and check it for problems, reporting all errors instead of stopping at the first
+pub fn validate_tests(test_files: &[PathBuf]) -> usize {
+    let mut errors = 0;
+
+    for test_file in test_files {
+        let problems = match spec::load(test_file) {
+            Ok((test, extensions)) => check_spec(&test, &extensions),
+            Err(e) => vec![e.to_string()],
+        };
+
+        if problems.is_empty() {
+            println!("  {} {}", "✓".green(), test_file.display());
+            continue;
+        }
+
+        errors += problems.len();
+        println!("  {} {}", "✗".red().bold(), test_file.display());
+        for problem in problems {
+            println!("      {}", problem.red());
+        }
+    }
+
+    errors
+}
+
+fn region_size(region: [[i32; 3]; 2]) -> [i32; 3] {
+    [
+        (region[1][0] - region[0][0]).abs() + 1,
+        (region[1][1] - region[0][1]).abs() + 1,
+        (region[1][2] - region[0][2]).abs() + 1,
+    ]
+}
+
+fn check_spec(test: &TestSpec, extensions: &SpecExtensions) -> Vec<String> {
+    let region = test.cleanup_region();
+    let mut problems = Vec::new();
+
+    let extension_positions = extensions
+        .actions
+        .iter()
+        .flat_map(|entry| entry.action.positions());
+    for (action, pos) in spec_positions(test).into_iter().chain(extension_positions) {
+        if !region_contains(region, pos) {
+            problems.push(format!(
+                "{} at [{}, {}, {}] is outside cleanup region [{}, {}, {}] to [{}, {}, {}]",
+                action,
+                pos[0],
+                pos[1],
+                pos[2],
+                region[0][0],
+                region[0][1],
+                region[0][2],
+                region[1][0],
+                region[1][1],
+                region[1][2]
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Every local position touched by the test's timeline, labelled with the action using it
+fn spec_positions(test: &TestSpec) -> Vec<(&'static str, [i32; 3])> {
+    let mut positions = Vec::new();
+
+    for entry in &test.timeline {
+        match &entry.action_type {
+            ActionType::Place { pos, .. } => positions.push(("place", *pos)),
+            ActionType::PlaceEach { blocks } => {
+                for placement in blocks {
+                    positions.push(("place_each", placement.pos));
+                }
+            }
+            ActionType::Fill { region, .. } => {
+                positions.push(("fill", region[0]));
+                positions.push(("fill", region[1]));
+            }
+            ActionType::Remove { pos } => positions.push(("remove", *pos)),
+            ActionType::Assert { checks } => {
+                for check in checks {
+                    positions.push(("assert", check.pos));
+                }
+            }
+            ActionType::AssertState { pos, .. } => positions.push(("assert_state", *pos)),
+        }
+    }
+
+    positions
+}
+
+fn region_contains(region: [[i32; 3]; 2], pos: [i32; 3]) -> bool {
+    (0..3).all(|axis| {
+        let min = region[0][axis].min(region[1][axis]);
+        let max = region[0][axis].max(region[1][axis]);
+        pos[axis] >= min && pos[axis] <= max
+    })
+}
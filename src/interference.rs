@@ -0,0 +1,254 @@
+//! Detecting blocks that tests change outside their cleanup regions
+
+use crate::bot::BlockChange;
+use std::collections::{HashMap, HashSet};
+
+/// A test changing a block outside its cleanup region
+pub struct Leak {
+    /// Index of the test the change is blamed on
+    pub leaker: usize,
+    /// Index of the test whose region the change landed in, if any
+    pub victim: Option<usize>,
+    /// World position of the first changed block
+    pub pos: [i32; 3],
+    pub block: String,
+}
+
+/// Links block changes during a wave to the tests whose regions they leave or enter
+///
+/// A change is blamed on a test when that test's actions wrote the block, when it spreads
+/// a block the test already leaked, or when it is outside every region and touches only
+/// that test's region. Anything else is left alone: a change inside a region belongs to
+/// its own test, and one that touches no region is not caused by a test.
+pub struct LeakDetector {
+    /// Cleanup regions in world coordinates, by test index
+    regions: Vec<[[i32; 3]; 2]>,
+    /// Regions in world coordinates the tests' actions placed blocks in, by test index
+    writes: Vec<(usize, [[i32; 3]; 2])>,
+    /// Positions changed by a leak, with the test blamed and the block type that leaked
+    leaked: HashMap<[i32; 3], (usize, String)>,
+    /// Leaker and victim pairs already reported, so a spreading leak is reported once
+    reported: HashSet<(usize, Option<usize>)>,
+}
+
+impl LeakDetector {
+    pub fn new(regions: Vec<[[i32; 3]; 2]>) -> Self {
+        Self {
+            regions,
+            writes: Vec::new(),
+            leaked: HashMap::new(),
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Note that a test's action places blocks in a world region
+    pub fn wrote(&mut self, test_idx: usize, region: [[i32; 3]; 2]) {
+        self.writes.push((test_idx, region));
+    }
+
+    /// Check changed blocks, returning leaks not reported before
+    pub fn check(&mut self, changes: Vec<BlockChange>) -> Vec<Leak> {
+        let mut leaks = Vec::new();
+
+        for BlockChange { pos, block, .. } in changes {
+            let owner = self.owner(pos);
+            let Some(leaker) = self.cause(pos, &block, owner) else {
+                continue;
+            };
+
+            self.leaked
+                .insert(pos, (leaker, block_type(&block).to_string()));
+            if self.reported.insert((leaker, owner)) {
+                leaks.push(Leak {
+                    leaker,
+                    victim: owner,
+                    pos,
+                    block,
+                });
+            }
+        }
+
+        leaks
+    }
+
+    /// Test whose region holds `pos`
+    fn owner(&self, pos: [i32; 3]) -> Option<usize> {
+        (0..self.regions.len()).find(|test_idx| distance(self.regions[*test_idx], pos) == 0)
+    }
+
+    /// Test other than the region's own that caused a change, if any
+    fn cause(&self, pos: [i32; 3], block: &str, owner: Option<usize>) -> Option<usize> {
+        // An action writing outside its own test's region leaks wherever it lands
+        if let Some(writer) = self.writer(pos, owner) {
+            return Some(writer);
+        }
+        if let Some(leaker) = self
+            .leaked_neighbour(pos, owner.map(|_| block_type(block)))
+            .filter(|leaker| Some(*leaker) != owner)
+        {
+            return Some(leaker);
+        }
+        match owner {
+            Some(_) => None,
+            None => self.only_touching(pos),
+        }
+    }
+
+    /// Test whose action last wrote `pos`, unless it is the region's own test
+    fn writer(&self, pos: [i32; 3], owner: Option<usize>) -> Option<usize> {
+        self.writes
+            .iter()
+            .rev()
+            .find(|(_, region)| distance(*region, pos) == 0)
+            .map(|(writer, _)| *writer)
+            .filter(|writer| Some(*writer) != owner)
+    }
+
+    /// Test that leaked a block next to `pos`. Inside a region only a block of the same type
+    /// counts, as when water flows on, so the region's own changes next to a leak are not
+    /// blamed on it.
+    fn leaked_neighbour(&self, pos: [i32; 3], same_type: Option<&str>) -> Option<usize> {
+        neighbours(pos).find_map(|neighbour| {
+            let (leaker, leaked_type) = self.leaked.get(&neighbour)?;
+            same_type
+                .is_none_or(|block_type| block_type == leaked_type)
+                .then_some(*leaker)
+        })
+    }
+
+    /// The single test whose region `pos` touches from outside
+    fn only_touching(&self, pos: [i32; 3]) -> Option<usize> {
+        let mut touching =
+            (0..self.regions.len()).filter(|test_idx| distance(self.regions[*test_idx], pos) == 1);
+        let first = touching.next()?;
+        touching.next().is_none().then_some(first)
+    }
+}
+
+/// The 26 positions around `pos`
+fn neighbours(pos: [i32; 3]) -> impl Iterator<Item = [i32; 3]> {
+    (-1..=1).flat_map(move |dx| {
+        (-1..=1).flat_map(move |dy| {
+            (-1..=1)
+                .map(move |dz| [pos[0] + dx, pos[1] + dy, pos[2] + dz])
+                .filter(move |neighbour| *neighbour != pos)
+        })
+    })
+}
+
+/// Block name from azalea's debug representation, e.g. `Water` from
+/// `BlockState(id: 86, Water { level: _1 })`
+fn block_type(state: &str) -> &str {
+    let block = state.split_once(", ").map_or(state, |(_, block)| block);
+    block.split([' ', ')']).next().unwrap_or(block)
+}
+
+/// Blocks between `pos` and the nearest block of a region along the farthest axis, 0 inside it
+fn distance(region: [[i32; 3]; 2], pos: [i32; 3]) -> i32 {
+    (0..3)
+        .map(|axis| {
+            let min = region[0][axis].min(region[1][axis]);
+            let max = region[0][axis].max(region[1][axis]);
+            (min - pos[axis]).max(pos[axis] - max).max(0)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATER: &str = "BlockState(id: 86, Water { level: _1 })";
+    const WIRE: &str = "BlockState(id: 2954, RedstoneWire { power: _15 })";
+
+    fn change(pos: [i32; 3], block: &str) -> BlockChange {
+        BlockChange {
+            pos,
+            block: block.to_string(),
+            tick: None,
+        }
+    }
+
+    /// Two 5x5x5 regions with a gap of 5 blocks along X
+    fn detector() -> LeakDetector {
+        LeakDetector::new(vec![[[0, 0, 0], [4, 4, 4]], [[10, 0, 0], [14, 4, 4]]])
+    }
+
+    #[test]
+    fn changes_inside_a_region_are_its_own() {
+        let mut leaks = detector();
+        assert!(leaks.check(vec![change([2, 2, 2], WATER)]).is_empty());
+        assert!(leaks.check(vec![change([12, 2, 2], WIRE)]).is_empty());
+    }
+
+    #[test]
+    fn change_next_to_a_region_leaks_from_it() {
+        let mut leaks = detector();
+        let found = leaks.check(vec![change([5, 2, 2], WATER)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].leaker, 0);
+        assert_eq!(found[0].victim, None);
+        assert_eq!(found[0].pos, [5, 2, 2]);
+    }
+
+    #[test]
+    fn unrelated_change_nearby_is_ignored() {
+        let mut leaks = detector();
+        assert!(leaks.check(vec![change([7, 2, 2], WATER)]).is_empty());
+        assert!(leaks.check(vec![change([2, 2, 7], WATER)]).is_empty());
+    }
+
+    #[test]
+    fn spreading_leak_is_followed_and_reported_once() {
+        let mut leaks = detector();
+        assert_eq!(leaks.check(vec![change([5, 2, 2], WATER)]).len(), 1);
+        let spread = leaks.check(vec![change([6, 2, 2], WATER), change([7, 2, 2], WATER)]);
+        assert!(spread.is_empty());
+        assert_eq!(
+            leaks.leaked.get(&[7, 2, 2]).map(|(leaker, _)| *leaker),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn leak_spreading_into_a_neighbour_names_it() {
+        let mut leaks = detector();
+        let changes = (5..=10).map(|x| change([x, 2, 2], WATER)).collect();
+        let found = leaks.check(changes);
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[1].leaker, found[1].victim), (0, Some(1)));
+        assert_eq!(found[1].pos, [10, 2, 2]);
+    }
+
+    #[test]
+    fn write_landing_in_a_neighbour_region_leaks() {
+        let mut leaks = detector();
+        leaks.wrote(0, [[12, 2, 2], [12, 2, 2]]);
+        let found = leaks.check(vec![change([12, 2, 2], WATER)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].leaker, found[0].victim), (0, Some(1)));
+    }
+
+    #[test]
+    fn victims_own_changes_next_to_a_leak_are_not_blamed() {
+        let mut leaks = detector();
+        leaks.wrote(0, [[9, 2, 2], [9, 2, 2]]);
+        assert_eq!(leaks.check(vec![change([9, 2, 2], WATER)]).len(), 1);
+        // The neighbour's own wire changes power right next to the leaked water
+        assert!(leaks.check(vec![change([10, 2, 2], WIRE)]).is_empty());
+    }
+
+    #[test]
+    fn test_writing_its_own_region_does_not_leak() {
+        let mut leaks = detector();
+        leaks.wrote(1, [[10, 0, 0], [14, 4, 4]]);
+        assert!(leaks.check(vec![change([11, 1, 1], WATER)]).is_empty());
+    }
+
+    #[test]
+    fn block_type_reads_azalea_states() {
+        assert_eq!(block_type(WATER), "Water");
+        assert_eq!(block_type("BlockState(id: 0, Air)"), "Air");
+    }
+}
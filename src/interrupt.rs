@@ -0,0 +1,40 @@
+//! Ctrl-C handling shared by every phase of a run
+//!
+//! Once anything listens for Ctrl-C, SIGINT no longer ends the process. So one handler is
+//! installed at startup, and every long-running phase checks it instead of listening itself:
+//! the first Ctrl-C asks the run to stop and clean up, the second exits immediately.
+
+use crate::error::EXIT_INTERRUPTED;
+use std::sync::OnceLock;
+use tokio::sync::watch;
+
+/// How often Ctrl-C has been pressed
+static PRESSES: OnceLock<watch::Sender<u32>> = OnceLock::new();
+
+fn presses() -> &'static watch::Sender<u32> {
+    PRESSES.get_or_init(|| watch::Sender::new(0))
+}
+
+/// Count every Ctrl-C from now on, exiting on the second
+pub fn install() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            presses().send_modify(|count| *count += 1);
+            if *presses().borrow() > 1 {
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+        }
+    });
+}
+
+/// Whether Ctrl-C has been pressed
+pub fn is_requested() -> bool {
+    *presses().borrow() > 0
+}
+
+/// Resolves once Ctrl-C has been pressed, at once if it already was
+pub async fn requested() {
+    let mut receiver = presses().subscribe();
+    // The sender lives in a static, so the channel never closes
+    let _ = receiver.wait_for(|count| *count > 0).await;
+}
@@ -0,0 +1,237 @@
+//! Placing tests side by side in the world without their cleanup regions overlapping
+
+use crate::config::LayoutConfig;
+use anyhow::Result;
+use colored::Colorize;
+use flint_core::test_spec::TestSpec;
+
+/// Offsets for every test, in order, checked so that no two cleanup regions overlap
+///
+/// With a fixed `spacing` tests sit on a square grid, otherwise they are packed in rows by
+/// the real size of their cleanup regions.
+pub fn place(tests: &[TestSpec], config: &LayoutConfig) -> Result<Vec<[i32; 3]>> {
+    let regions: Vec<[[i32; 3]; 2]> = tests
+        .iter()
+        .map(|test| bounds(test.cleanup_region()))
+        .collect();
+    let corners = match config.spacing {
+        Some(spacing) => grid_corners(&regions, spacing),
+        None => packed_corners(&regions, config.gap.max(0)),
+    };
+
+    let offsets: Vec<[i32; 3]> = regions
+        .iter()
+        .zip(corners)
+        .map(|([min, _], [x, z])| {
+            [
+                config.origin[0] + x - min[0],
+                config.y.map_or(0, |y| y - min[1]),
+                config.origin[1] + z - min[2],
+            ]
+        })
+        .collect();
+
+    let world: Vec<[[i32; 3]; 2]> = regions
+        .iter()
+        .zip(&offsets)
+        .map(|(region, offset)| translate(*region, *offset))
+        .collect();
+    for first in 0..world.len() {
+        for second in first + 1..world.len() {
+            if overlap(world[first], world[second]) {
+                anyhow::bail!(
+                    "Cleanup regions of {} and {} overlap in the layout, increase [layout] spacing",
+                    tests[first].name,
+                    tests[second].name
+                );
+            }
+        }
+    }
+
+    Ok(offsets)
+}
+
+/// Print where every test's cleanup region ends up in the world
+pub fn print_map(tests_with_offsets: &[(TestSpec, [i32; 3])]) {
+    let world: Vec<[[i32; 3]; 2]> = tests_with_offsets
+        .iter()
+        .map(|(test, offset)| translate(bounds(test.cleanup_region()), *offset))
+        .collect();
+    let Some(&[mut min, mut max]) = world.first() else {
+        return;
+    };
+    for [region_min, region_max] in &world {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(region_min[axis]);
+            max[axis] = max[axis].max(region_max[axis]);
+        }
+    }
+
+    println!(
+        "  {} Layout: {} x {} blocks from [{}, {}] to [{}, {}]",
+        "→".blue(),
+        max[0] - min[0] + 1,
+        max[2] - min[2] + 1,
+        min[0],
+        min[2],
+        max[0],
+        max[2]
+    );
+    let total_tests = tests_with_offsets.len();
+    for (test_index, ((test, _), [region_min, region_max])) in
+        tests_with_offsets.iter().zip(&world).enumerate()
+    {
+        println!(
+            "    {} {} [{}, {}, {}] to [{}, {}, {}]",
+            format!("[{}/{}]", test_index + 1, total_tests).dimmed(),
+            test.name,
+            region_min[0],
+            region_min[1],
+            region_min[2],
+            region_max[0],
+            region_max[1],
+            region_max[2]
+        );
+    }
+}
+
+/// Corners, as `[x, z]`, on a square grid with fixed spacing between neighbouring tests
+fn grid_corners(regions: &[[[i32; 3]; 2]], spacing: i32) -> Vec<[i32; 2]> {
+    let columns = (regions.len() as f64).sqrt().ceil().max(1.0) as usize;
+    (0..regions.len())
+        .map(|test_index| {
+            let column = (test_index % columns) as i32;
+            let row = (test_index / columns) as i32;
+            [column * spacing, row * spacing]
+        })
+        .collect()
+}
+
+/// Corners, as `[x, z]`, filling rows about as wide as the whole layout is deep. Tests stay
+/// in order, so neighbours in the list are neighbours in the world.
+fn packed_corners(regions: &[[[i32; 3]; 2]], gap: i32) -> Vec<[i32; 2]> {
+    let sizes: Vec<[i32; 2]> = regions
+        .iter()
+        .map(|[min, max]| [max[0] - min[0] + 1 + gap, max[2] - min[2] + 1 + gap])
+        .collect();
+    let area: i64 = sizes.iter().map(|[x, z]| *x as i64 * *z as i64).sum();
+    let widest = sizes.iter().map(|[x, _]| *x).max().unwrap_or(0);
+    let row_width = ((area as f64).sqrt().ceil() as i32).max(widest);
+
+    let mut corners = Vec::new();
+    let (mut x, mut z, mut row_depth) = (0, 0, 0);
+    for [width, depth] in sizes {
+        if x > 0 && x + width > row_width {
+            z += row_depth;
+            x = 0;
+            row_depth = 0;
+        }
+        corners.push([x, z]);
+        x += width;
+        row_depth = row_depth.max(depth);
+    }
+    corners
+}
+
+/// A region with its corners ordered as minimum and maximum
+fn bounds(region: [[i32; 3]; 2]) -> [[i32; 3]; 2] {
+    [
+        std::array::from_fn(|axis| region[0][axis].min(region[1][axis])),
+        std::array::from_fn(|axis| region[0][axis].max(region[1][axis])),
+    ]
+}
+
+fn translate([min, max]: [[i32; 3]; 2], offset: [i32; 3]) -> [[i32; 3]; 2] {
+    [
+        std::array::from_fn(|axis| min[axis] + offset[axis]),
+        std::array::from_fn(|axis| max[axis] + offset[axis]),
+    ]
+}
+
+fn overlap(first: [[i32; 3]; 2], second: [[i32; 3]; 2]) -> bool {
+    (0..3).all(|axis| first[0][axis] <= second[1][axis] && second[0][axis] <= first[1][axis])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A test whose cleanup region has the given size, starting at Y 60
+    fn test(name: &str, size: [i32; 3]) -> TestSpec {
+        let max = [size[0] - 1, 60 + size[1] - 1, size[2] - 1];
+        serde_json::from_value(serde_json::json!({
+            "flintVersion": "0.1",
+            "name": name,
+            "setup": { "cleanup": { "region": [[0, 60, 0], max] } },
+            "timeline": [
+                { "at": 0, "do": "place", "pos": [0, 60, 0], "block": "minecraft:stone" }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn world_regions(tests: &[TestSpec], offsets: &[[i32; 3]]) -> Vec<[[i32; 3]; 2]> {
+        tests
+            .iter()
+            .zip(offsets)
+            .map(|(test, offset)| translate(bounds(test.cleanup_region()), *offset))
+            .collect()
+    }
+
+    /// Free blocks between two regions along the axis that separates them the most
+    fn separation(first: [[i32; 3]; 2], second: [[i32; 3]; 2]) -> i32 {
+        [0, 2]
+            .into_iter()
+            .map(|axis| {
+                (second[0][axis] - first[1][axis] - 1).max(first[0][axis] - second[1][axis] - 1)
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn packed_tests_keep_the_gap_between_them() {
+        let tests = vec![
+            test("large", [20, 5, 12]),
+            test("small", [3, 3, 3]),
+            test("long", [4, 2, 30]),
+            test("wide", [25, 4, 5]),
+            test("cube", [9, 9, 9]),
+        ];
+        let config = LayoutConfig::default();
+        let offsets = place(&tests, &config).unwrap();
+        let world = world_regions(&tests, &offsets);
+
+        for first in 0..world.len() {
+            for second in first + 1..world.len() {
+                assert!(!overlap(world[first], world[second]));
+                assert!(separation(world[first], world[second]) >= config.gap);
+            }
+        }
+    }
+
+    #[test]
+    fn layout_starts_at_the_origin_and_y() {
+        let tests = vec![test("first", [5, 5, 5]), test("second", [5, 5, 5])];
+        let config = LayoutConfig {
+            origin: [100, -50],
+            y: Some(-20),
+            ..Default::default()
+        };
+        let offsets = place(&tests, &config).unwrap();
+        let world = world_regions(&tests, &offsets);
+
+        assert_eq!(world[0][0], [100, -20, -50]);
+        assert_eq!(world[1][0][1], -20);
+    }
+
+    #[test]
+    fn grid_too_tight_for_the_tests_is_rejected() {
+        let tests = vec![test("first", [10, 2, 10]), test("second", [10, 2, 10])];
+        let config = LayoutConfig {
+            spacing: Some(4),
+            ..Default::default()
+        };
+        assert!(place(&tests, &config).is_err());
+    }
+}
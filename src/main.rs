@@ -1,30 +1,161 @@
+mod backup;
 mod bot;
+mod compare;
+mod config;
+mod dap;
+mod debugger;
+mod error;
 mod executor;
+mod inspect;
+mod interference;
+mod interrupt;
+mod layout;
+mod record;
+mod report;
+mod server;
+mod shard;
+mod snapshot;
+mod spec;
+mod structure;
+mod timing;
+mod watch;
+mod world;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
+use config::{Config, Reporter};
 use flint_core::loader::TestLoader;
-use flint_core::spatial::calculate_test_offset_default;
 use flint_core::test_spec::TestSpec;
+use report::TestOutcome;
 use std::path::PathBuf;
+use std::time::Duration;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
 #[command(name = "flintmc")]
 #[command(about = "Minecraft server testing framework", long_about = None)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to a config file (defaults to the nearest flintmc.toml)
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Named profile from the config file, e.g. [profile.ci]
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run tests against a server (the default when no subcommand is given)
+    Run(RunArgs),
+    /// List discovered tests without connecting to a server
+    List(SelectArgs),
+    /// Parse and check every test spec without connecting to a server
+    Validate(SelectArgs),
+    /// Serve the Debug Adapter Protocol over stdio for stepping tests from an editor
+    Dap,
+    /// Record a player building in a region and write it as a test spec
+    Record(RecordArgs),
+    /// Run the suite against two servers and report where the observed states differ
+    Compare(CompareArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    #[command(flatten)]
+    select: SelectArgs,
+
+    /// The two servers to compare, e.g. --server vanilla:25565 --server fork:25566
+    #[arg(short, long, required = true)]
+    server: Vec<String>,
+
+    /// Also write the differences as JSON
+    #[arg(long, value_name = "FILE")]
+    report_path: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct RecordArgs {
+    /// Region to watch, in world coordinates
+    #[arg(long, num_args = 6, required = true, allow_negative_numbers = true)]
+    #[arg(value_names = ["X1", "Y1", "Z1", "X2", "Y2", "Z2"])]
+    region: Vec<i32>,
+
+    /// Where to write the generated test spec
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+
+    /// Test name (defaults to the output file name)
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Server address (e.g., localhost:25565)
+    #[arg(short, long)]
+    server: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SelectArgs {
     /// Path to test file or directory
     #[arg(value_name = "PATH")]
     path: Option<PathBuf>,
 
-    /// Server address (e.g., localhost:25565)
-    #[arg(short, long)]
-    server: String,
+    /// Recursively search directories for test files (--recursive=false overrides the config)
+    #[arg(short, long, num_args = 0..=1, default_missing_value = "true", require_equals = true)]
+    recursive: Option<bool>,
 
-    /// Recursively search directories for test files
+    /// Filter tests by tags (can be specified multiple times)
+    #[arg(short = 't', long = "tag")]
+    tags: Vec<String>,
+}
+
+impl SelectArgs {
+    /// Whether to search directories recursively, the flag taking precedence over the config
+    fn recursive(&self, config: &Config) -> bool {
+        self.recursive.unwrap_or(config.recursive)
+    }
+
+    /// Tags tests are filtered by: the command line's, otherwise the config's unless a path
+    /// is given
+    fn tags<'a>(&'a self, config: &'a Config) -> &'a [String] {
+        if !self.tags.is_empty() {
+            &self.tags
+        } else if self.path.is_some() {
+            &[]
+        } else {
+            &config.tags
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    #[command(flatten)]
+    select: SelectArgs,
+
+    /// Server address (e.g., localhost:25565); repeat to shard tests across servers
     #[arg(short, long)]
-    recursive: bool,
+    server: Vec<String>,
+
+    /// Start this server jar for the run instead of connecting to a running server
+    #[arg(long, value_name = "JAR", conflicts_with = "server")]
+    server_jar: Option<PathBuf>,
+
+    /// Working directory for the --server-jar server (world, logs, server.properties)
+    #[arg(long, value_name = "DIR")]
+    server_dir: Option<PathBuf>,
+
+    /// Start this many --server-jar servers and shard the tests across them
+    #[arg(long, value_name = "N", conflicts_with = "server")]
+    shards: Option<usize>,
 
     /// Break after test setup (cleanup phase) to allow manual inspection
     #[arg(long)]
@@ -34,31 +165,129 @@ struct Args {
     #[arg(long)]
     chat_control: bool,
 
-    /// Filter tests by tags (can be specified multiple times)
-    #[arg(short = 't', long = "tag")]
-    tags: Vec<String>,
+    /// How to report results
+    #[arg(long, value_enum)]
+    reporter: Option<Reporter>,
+
+    /// Where the JSON reporter writes its report
+    #[arg(long, value_name = "FILE")]
+    report_path: Option<PathBuf>,
+
+    /// Re-run failed tests alone up to N times; tests that then pass are reported as flaky
+    #[arg(long, value_name = "N")]
+    retries: Option<u32>,
+
+    /// Print where the time went: setup, cleanup, polling, MSPT per sprint, slowest assertions
+    #[arg(long)]
+    timings: bool,
+
+    /// Write the timings as JSON
+    #[arg(long, value_name = "FILE")]
+    timings_path: Option<PathBuf>,
+
+    /// Stay connected and re-run tests whenever their JSON files change
+    #[arg(long)]
+    watch: bool,
+
+    /// Write the regions checked by assert_snapshot as the new golden snapshots
+    #[arg(long)]
+    update_snapshots: bool,
+
+    /// Run on an existing map: save every test area first and place it back afterwards
+    #[arg(long)]
+    preserve_world: bool,
+
+    /// Also break at the end of this tick (used by `flintmc dap`)
+    #[arg(long, value_name = "TICK", hide = true)]
+    break_at: Vec<u32>,
+
+    /// Print machine-readable stop events (used by `flintmc dap`)
+    #[arg(long, hide = true)]
+    machine_events: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     // Setup logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    // DAP owns stdout, so logs go to stderr and nothing else may be printed
+    if let Some(Command::Dap) = cli.command {
+        tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
+            .with_writer(std::io::stderr)
+            .init();
+        return dap::serve(cli.config, cli.profile).await;
+    }
 
-    let args = Args::parse();
+    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    interrupt::install();
 
     println!("{}", "FlintMC - Minecraft Testing Framework".green().bold());
     println!();
 
-    // Collect test files - use tags if provided, otherwise use path
-    let test_files = if !args.tags.is_empty() {
-        println!("{} Filtering by tags: {:?}", "→".blue(), args.tags);
-        TestLoader::collect_by_tags(&args.tags)?
-    } else if let Some(ref path) = args.path {
-        TestLoader::collect_test_files(path, args.recursive)?
+    let config = Config::load(cli.config.as_deref(), cli.profile.as_deref())?;
+
+    match cli.command {
+        Some(Command::List(select)) => {
+            let test_files = collect_test_files(&select, &config)?;
+            println!("Found {} test file(s)\n", test_files.len());
+            let errors = inspect::list_tests(&test_files);
+            if errors > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::Validate(select)) => {
+            let test_files = collect_test_files(&select, &config)?;
+            println!("Validating {} test file(s)\n", test_files.len());
+            let errors = inspect::validate_tests(&test_files);
+            if errors > 0 {
+                println!("\n{} {} problem(s) found\n", "✗".red().bold(), errors);
+                std::process::exit(1);
+            }
+            println!("\n{} All test files are valid\n", "✓".green());
+            Ok(())
+        }
+        Some(Command::Record(args)) => infrastructure_exit(record(args, &config).await),
+        Some(Command::Compare(args)) => infrastructure_exit(compare(args, &config).await),
+        Some(Command::Run(args)) => infrastructure_exit(run(&args, &config).await),
+        Some(Command::Dap) => unreachable!("handled before loading the config"),
+        None => infrastructure_exit(run(&cli.run, &config).await),
+    }
+}
+
+/// A command that stops with an error tested or recorded nothing, so it exits with the
+/// infrastructure code
+fn infrastructure_exit(result: Result<()>) -> Result<()> {
+    if let Err(e) = result {
+        eprintln!("{} {:#}", "Error:".red().bold(), e);
+        std::process::exit(error::EXIT_INFRASTRUCTURE);
+    }
+    Ok(())
+}
+
+/// Collect test files - use tags if provided, otherwise use path
+/// Falls back to the tags and test roots from the config file
+fn collect_test_files(select: &SelectArgs, config: &Config) -> Result<Vec<PathBuf>> {
+    let tags = select.tags(config);
+    let recursive = select.recursive(config);
+
+    let test_files = if let Some(ref path) = select.path
+        && select.tags.is_empty()
+    {
+        TestLoader::collect_test_files(path, recursive)?
+    } else if !tags.is_empty() {
+        println!("{} Filtering by tags: {:?}", "→".blue(), tags);
+        TestLoader::collect_by_tags(tags)?
+    } else if !config.paths.is_empty() {
+        let mut test_files = Vec::new();
+        for root in &config.paths {
+            test_files.extend(TestLoader::collect_test_files(root, recursive)?);
+        }
+        test_files
     } else {
         eprintln!(
             "{} Must specify either a path or tags to filter by",
@@ -68,19 +297,361 @@ async fn main() -> Result<()> {
     };
 
     if test_files.is_empty() {
-        let location = if !args.tags.is_empty() {
-            format!("with tags: {:?}", args.tags)
+        let location = if let Some(ref path) = select.path
+            && select.tags.is_empty()
+        {
+            format!("at: {}", path.display())
+        } else if !tags.is_empty() {
+            format!("with tags: {:?}", tags)
         } else {
-            format!("at: {}", args.path.as_ref().unwrap().display())
+            format!("in: {:?}", config.paths)
         };
         eprintln!("{} No test files found {}", "Error:".red().bold(), location);
         std::process::exit(1);
     }
 
+    Ok(test_files)
+}
+
+async fn run(args: &RunArgs, config: &Config) -> Result<()> {
+    // Explicit addresses win, then an explicit jar, then the config file
+    let servers = if args.server.is_empty() {
+        config.server.iter().cloned().collect()
+    } else {
+        args.server.clone()
+    };
+    let server_jar = args.server_jar.clone().or_else(|| {
+        servers
+            .is_empty()
+            .then(|| config.managed_server.jar.clone())
+            .flatten()
+    });
+    if servers.is_empty() && server_jar.is_none() {
+        eprintln!(
+            "{} Must specify a server address with --server, a jar with --server-jar, or either in {}",
+            "Error:".red().bold(),
+            config::CONFIG_FILE_NAME
+        );
+        std::process::exit(1);
+    }
+
+    let shard_count = match &server_jar {
+        Some(_) => args.shards.unwrap_or(1).max(1),
+        None => servers.len(),
+    };
+    if args.shards.is_some() && server_jar.is_none() {
+        eprintln!(
+            "{} --shards starts managed servers and needs --server-jar",
+            "Error:".red().bold()
+        );
+        std::process::exit(1);
+    }
+    let mut distinct_servers = servers.clone();
+    distinct_servers.sort();
+    distinct_servers.dedup();
+    if distinct_servers.len() != servers.len() {
+        // Tick freezing and sprinting affect the whole server, so shards cannot share one
+        eprintln!(
+            "{} Each --server may only be given once",
+            "Error:".red().bold()
+        );
+        std::process::exit(1);
+    }
+    if shard_count > 1
+        && (args.watch || args.break_after_setup || args.chat_control || !args.break_at.is_empty())
+    {
+        eprintln!(
+            "{} --watch and debugging need a single server",
+            "Error:".red().bold()
+        );
+        std::process::exit(1);
+    }
+
+    let test_files = collect_test_files(&args.select, config)?;
+
     println!("Found {} test file(s)\n", test_files.len());
 
-    // Connect to server
-    let mut executor = executor::TestExecutor::new();
+    // Load all tests before starting anything
+    let tests = load_tests(&test_files);
+    if args.preserve_world || config.preserve_world {
+        check_backups(&tests);
+    }
+
+    // Start the managed servers only once the tests are known to load
+    let mut managed = Vec::new();
+    let servers = match server_jar {
+        Some(jar) => {
+            let starts = (0..shard_count).map(|shard_index| {
+                let mut server_config = config.managed_server.clone();
+                if let Some(dir) = &args.server_dir {
+                    server_config.dir = dir.clone();
+                }
+                if shard_count > 1 {
+                    server_config.dir = server_config.dir.join(format!("shard-{}", shard_index));
+                    // Every shard needs its own port
+                    server_config.port = None;
+                }
+                let jar = jar.clone();
+                async move {
+                    let mut managed_server =
+                        server::ManagedServer::start(&jar, &server_config).await?;
+                    managed_server.op(&config.bot.username).await?;
+                    anyhow::Ok(managed_server)
+                }
+            });
+            let mut failure = None;
+            for started in futures::future::join_all(starts).await {
+                match started {
+                    Ok(managed_server) => managed.push(managed_server),
+                    Err(e) => failure = failure.or(Some(e)),
+                }
+            }
+            if let Some(e) = failure {
+                stop_servers(managed).await?;
+                if interrupt::is_requested() {
+                    std::process::exit(error::EXIT_INTERRUPTED);
+                }
+                return Err(e);
+            }
+            managed.iter().map(|server| server.address()).collect()
+        }
+        None => servers,
+    };
+
+    let shards = shard::distribute(tests, servers.len());
+    if shards.len() > 1 {
+        println!("{} Sharding across {} servers:", "→".blue(), shards.len());
+        for (server, shard) in servers.iter().zip(&shards) {
+            println!(
+                "  {} {}: {} test(s), ~{} ticks",
+                "→".blue(),
+                server,
+                shard.tests.len(),
+                shard.ticks
+            );
+        }
+        println!();
+    }
+
+    let retries = args.retries.unwrap_or(config.retries);
+
+    // Every shard gets its own bot and runs concurrently with the others
+    let runs = servers
+        .iter()
+        .zip(shards)
+        .map(|(server, shard)| async move {
+            // Tests that measure tick time go last, each in a wave of its own
+            let (mut parallel, isolated): (Vec<_>, Vec<_>) = shard
+                .tests
+                .into_iter()
+                .zip(shard.extensions)
+                .partition(|(_, extensions)| !extensions.needs_isolation());
+            // The bot watches one dimension at a time, so tests sharing one share waves
+            parallel.sort_by(|(_, a), (_, b)| a.dimension().cmp(b.dimension()));
+            let parallel_count = parallel.len();
+            let (tests, extensions): (Vec<TestSpec>, Vec<spec::SpecExtensions>) =
+                parallel.into_iter().chain(isolated).unzip();
+
+            let total_tests = tests.len();
+            let offsets = layout::place(&tests, &config.layout)?;
+            let tests_with_offsets: Vec<(TestSpec, [i32; 3])> =
+                tests.into_iter().zip(offsets).collect();
+            layout::print_map(&tests_with_offsets);
+            println!();
+
+            let mut executor = new_executor(&args, config);
+
+            println!("{} Connecting to {}...", "→".blue(), server);
+            executor.connect(server).await?;
+            println!("{} Connected successfully\n", "✓".green());
+
+            // Run all tests of the shard in parallel using merged timeline
+            let mut break_after_setup = args.break_after_setup;
+            let mut results = Vec::new();
+            if parallel_count > 0 {
+                results = executor
+                    .run_tests_parallel(
+                        &tests_with_offsets[..parallel_count],
+                        &extensions[..parallel_count],
+                        break_after_setup,
+                    )
+                    .await?;
+                break_after_setup = false;
+            }
+            for test_index in parallel_count..total_tests {
+                if executor.interrupted() {
+                    break;
+                }
+                println!(
+                    "\n{} Running {} alone",
+                    "→".blue().bold(),
+                    tests_with_offsets[test_index].0.name
+                );
+                results.extend(
+                    executor
+                        .run_tests_parallel(
+                            std::slice::from_ref(&tests_with_offsets[test_index]),
+                            std::slice::from_ref(&extensions[test_index]),
+                            break_after_setup,
+                        )
+                        .await?,
+                );
+                break_after_setup = false;
+            }
+            let mut outcomes: Vec<TestOutcome> = results
+                .into_iter()
+                .map(|result| {
+                    let errored = executor.is_errored(&result.test_name);
+                    TestOutcome::new(result, errored)
+                })
+                .collect();
+
+            // Failed tests get another chance, each alone in its own wave
+            for attempt in 1..=retries {
+                if executor.interrupted() {
+                    break;
+                }
+                let failing: Vec<usize> = (0..outcomes.len())
+                    .filter(|test_index| !outcomes[*test_index].result.success)
+                    .collect();
+                for test_index in failing {
+                    println!(
+                        "\n{} Retrying {} in isolation (attempt {}/{})",
+                        "↻".cyan().bold(),
+                        tests_with_offsets[test_index].0.name,
+                        attempt + 1,
+                        retries + 1
+                    );
+                    let retry = executor
+                        .run_tests_parallel(
+                            std::slice::from_ref(&tests_with_offsets[test_index]),
+                            std::slice::from_ref(&extensions[test_index]),
+                            false,
+                        )
+                        .await?;
+                    if let Some(result) = retry.into_iter().next() {
+                        let errored = executor.is_errored(&result.test_name);
+                        outcomes[test_index].retry(result, errored);
+                    }
+                }
+            }
+            anyhow::Ok((executor, outcomes))
+        });
+    // Managed servers are stopped whether or not the run succeeded
+    let finished = async {
+        // Every shard finishes and cleans up its server before an error is reported
+        let shard_runs = futures::future::join_all(runs)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut executors = Vec::new();
+        let mut outcomes = Vec::new();
+        let mut timings = timing::RunTimings::default();
+        let mut interrupted = false;
+        for (mut executor, shard_outcomes) in shard_runs {
+            interrupted |= executor.interrupted();
+            timings.merge(executor.take_timings());
+            executors.push(executor);
+            outcomes.extend(shard_outcomes);
+        }
+        for outcome in &mut outcomes {
+            outcome.quarantined = config.quarantine.contains(&outcome.result.test_name);
+        }
+
+        report::print_summary(&outcomes);
+        if args.timings {
+            timings.print_summary();
+        }
+        if let Some(timings_path) = &args.timings_path {
+            timings.write_json(timings_path)?;
+            println!(
+                "{} Timings written to {}\n",
+                "→".blue(),
+                timings_path.display()
+            );
+        }
+        let total_failed = outcomes
+            .iter()
+            .filter(|outcome| outcome.fails_run())
+            .count();
+        let total_errored = outcomes
+            .iter()
+            .filter(|outcome| outcome.errors_run())
+            .count();
+
+        if args.reporter.unwrap_or(config.reporter) == Reporter::Json {
+            let report_path = args
+                .report_path
+                .clone()
+                .or_else(|| config.report_path.clone())
+                .unwrap_or_else(|| PathBuf::from(report::DEFAULT_REPORT_PATH));
+            report::write_json_report(&report_path, &outcomes)?;
+            println!(
+                "{} Report written to {}\n",
+                "→".blue(),
+                report_path.display()
+            );
+        }
+
+        if args.watch
+            && !interrupted
+            && let Some(executor) = executors.first_mut()
+        {
+            let watch_paths = watch_paths(&args.select, config, &test_files);
+            // Runs until interrupted
+            watch::watch_tests(
+                executor,
+                &watch_paths,
+                args.select.recursive(config),
+                args.select.tags(config),
+                args.break_after_setup,
+                &config.layout,
+            )
+            .await?;
+        }
+        anyhow::Ok((interrupted, total_errored, total_failed))
+    }
+    .await;
+
+    let stopped = stop_servers(managed).await;
+    let (interrupted, total_errored, total_failed) = finished?;
+    stopped?;
+
+    if interrupted {
+        std::process::exit(error::EXIT_INTERRUPTED);
+    }
+    // Infrastructure errors take precedence so CI can tell a retry may help
+    if total_errored > 0 {
+        std::process::exit(error::EXIT_INFRASTRUCTURE);
+    }
+    if total_failed > 0 {
+        std::process::exit(error::EXIT_FAILED);
+    }
+
+    Ok(())
+}
+
+/// Save and stop every managed server, trying all of them before reporting the first error
+async fn stop_servers(managed: Vec<server::ManagedServer>) -> Result<()> {
+    let mut result = Ok(());
+    for managed_server in managed {
+        if let Err(e) = managed_server.stop().await {
+            result = result.and(Err(e));
+        }
+    }
+    result
+}
+
+/// Executor configured from the command line and config file, not yet connected
+fn new_executor(args: &RunArgs, config: &Config) -> executor::TestExecutor {
+    let mut executor = configured_executor(config);
+    executor.add_breakpoints(&args.break_at);
+    executor.set_machine_events(args.machine_events);
+    executor.set_update_snapshots(args.update_snapshots);
+    if args.preserve_world {
+        executor.set_preserve_world(true);
+    }
 
     // Enable chat control if requested
     if args.chat_control {
@@ -91,28 +662,43 @@ async fn main() -> Result<()> {
         );
     }
 
-    println!("{} Connecting to {}...", "→".blue(), args.server);
-    executor.connect(&args.server).await?;
-    println!("{} Connected successfully\n", "✓".green());
+    executor
+}
 
-    // Load all tests and calculate offsets
-    let total_tests = test_files.len();
-    let mut tests_with_offsets = Vec::new();
+/// Executor with the settings from the config file
+fn configured_executor(config: &Config) -> executor::TestExecutor {
+    let mut executor = executor::TestExecutor::new();
+    executor.set_username(&config.bot.username);
+    executor.set_polling(config.polling.attempts, config.polling.delay_ms);
+    executor.set_connect_timeout(Duration::from_secs(config.timeouts.connect_secs));
+    executor.set_rate_limit(config.commands.rate_limit());
+    executor.set_sprint_timeout(Duration::from_secs(config.timeouts.sprint_secs));
+    executor.set_chunk_timeout(Duration::from_secs(config.timeouts.chunk_secs));
+    executor.set_preserve_world(config.preserve_world);
+    executor.set_run_players(config.debugger.run_players.clone());
+    executor
+}
 
-    for (test_index, test_file) in test_files.iter().enumerate() {
-        match TestSpec::from_file(test_file) {
-            Ok(test) => {
-                let offset = calculate_test_offset_default(test_index, total_tests);
-                println!(
-                    "  {} Grid position: {} (offset: [{}, {}, {}])",
-                    "→".blue(),
-                    format!("[{}/{}]", test_index + 1, total_tests).dimmed(),
-                    offset[0],
-                    offset[1],
-                    offset[2]
-                );
-                tests_with_offsets.push((test, offset));
-            }
+/// Refuse to touch an existing map when a test area is too large to be saved first
+fn check_backups(tests: &[(TestSpec, spec::SpecExtensions)]) {
+    let mut too_large = 0;
+    for (test, _) in tests {
+        if let Err(e) = backup::check_size(test.cleanup_region()) {
+            eprintln!("{} {}: {}", "Error:".red().bold(), test.name, e);
+            too_large += 1;
+        }
+    }
+    if too_large > 0 {
+        std::process::exit(error::EXIT_INFRASTRUCTURE);
+    }
+}
+
+/// Load every test spec, exiting on the first one that fails to parse
+fn load_tests(test_files: &[PathBuf]) -> Vec<(TestSpec, spec::SpecExtensions)> {
+    let mut tests = Vec::new();
+    for test_file in test_files {
+        match spec::load(test_file) {
+            Ok(loaded) => tests.push(loaded),
             Err(e) => {
                 eprintln!(
                     "{} Failed to load test {}: {}",
@@ -120,45 +706,127 @@ async fn main() -> Result<()> {
                     test_file.display(),
                     e
                 );
-                std::process::exit(1);
+                std::process::exit(error::EXIT_INFRASTRUCTURE);
             }
         }
     }
+    tests
+}
 
-    println!();
+async fn compare(args: CompareArgs, config: &Config) -> Result<()> {
+    let [left, right] = args.server.as_slice() else {
+        eprintln!(
+            "{} compare needs exactly two --server addresses",
+            "Error:".red().bold()
+        );
+        std::process::exit(1);
+    };
+
+    let test_files = collect_test_files(&args.select, config)?;
+    println!("Found {} test file(s)\n", test_files.len());
 
-    // Run all tests in parallel using merged timeline
-    let results = executor
-        .run_tests_parallel(&tests_with_offsets, args.break_after_setup)
-        .await?;
+    // Both servers run the same tests at the same offsets, at the same time
+    let [left_run, right_run] = [left, right].map(|server| {
+        let (tests, extensions): (Vec<TestSpec>, Vec<spec::SpecExtensions>) =
+            load_tests(&test_files).into_iter().unzip();
+        let offsets = layout::place(&tests, &config.layout);
 
-    // Print summary
-    println!("\n{}", "═".repeat(60).dimmed());
-    println!("{}", "Test Summary".cyan().bold());
-    println!("{}", "═".repeat(60).dimmed());
+        async move {
+            let tests_with_offsets: Vec<(TestSpec, [i32; 3])> =
+                tests.into_iter().zip(offsets?).collect();
 
-    let total_passed = results.iter().filter(|r| r.success).count();
-    let total_failed = results.len() - total_passed;
+            let mut executor = configured_executor(config);
+            executor.set_record_observations(true);
 
-    for result in &results {
-        let status = if result.success {
-            "PASS".green().bold()
-        } else {
-            "FAIL".red().bold()
-        };
-        println!("  [{}] {}", status, result.test_name);
+            println!("{} Connecting to {}...", "→".blue(), server);
+            executor.connect(server).await?;
+            println!("{} Connected to {}\n", "✓".green(), server);
+
+            let results = executor
+                .run_tests_parallel(&tests_with_offsets, &extensions, false)
+                .await?;
+            if executor.interrupted() {
+                anyhow::bail!("Interrupted before {} finished", server);
+            }
+            anyhow::Ok((results, executor.take_observations()))
+        }
+    });
+    // Both servers finish and clean up before an error is reported
+    let (left_run, right_run) = futures::future::join(left_run, right_run).await;
+    let ((left_results, left_observations), (right_results, right_observations)) =
+        (left_run?, right_run?);
+
+    let servers = [left.as_str(), right.as_str()];
+    let differences = compare::diff(&left_observations, &right_observations);
+    compare::print_comparison(servers, [&left_results, &right_results], &differences);
+
+    if let Some(report_path) = &args.report_path {
+        compare::write_json_report(report_path, servers, &differences)?;
+        println!(
+            "{} Comparison written to {}\n",
+            "→".blue(),
+            report_path.display()
+        );
     }
 
-    println!(
-        "\n{} tests run: {} passed, {} failed\n",
-        results.len(),
-        total_passed.to_string().green(),
-        total_failed.to_string().red()
-    );
+    if !differences.is_empty() || !compare::status_changes(&left_results, &right_results).is_empty()
+    {
+        std::process::exit(error::EXIT_FAILED);
+    }
 
-    if total_failed > 0 {
+    Ok(())
+}
+
+async fn record(args: RecordArgs, config: &Config) -> Result<()> {
+    let Some(server) = args.server.clone().or_else(|| config.server.clone()) else {
+        eprintln!(
+            "{} Must specify a server address with --server or in {}",
+            "Error:".red().bold(),
+            config::CONFIG_FILE_NAME
+        );
         std::process::exit(1);
+    };
+
+    let name = args.name.clone().unwrap_or_else(|| {
+        args.output
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "recorded_test".to_string())
+    });
+    let region = [
+        [args.region[0], args.region[1], args.region[2]],
+        [args.region[3], args.region[4], args.region[5]],
+    ];
+
+    let mut bot = bot::TestBot::new();
+    bot.set_username(&config.bot.username);
+    bot.set_connect_timeout(Duration::from_secs(config.timeouts.connect_secs));
+    bot.set_rate_limit(config.commands.rate_limit());
+
+    println!("{} Connecting to {}...", "→".blue(), server);
+    bot.connect(&server).await?;
+    println!("{} Connected successfully\n", "✓".green());
+
+    record::record(&bot, region, &args.output, &name).await
+}
+
+/// Paths to watch for changes: the explicit path, the directories holding the selected
+/// tests when filtering by tags, or the test roots from the config file
+fn watch_paths(select: &SelectArgs, config: &Config, test_files: &[PathBuf]) -> Vec<PathBuf> {
+    if let Some(ref path) = select.path
+        && select.tags.is_empty()
+    {
+        return vec![path.clone()];
+    }
+    if select.tags.is_empty() && config.tags.is_empty() && !config.paths.is_empty() {
+        return config.paths.clone();
     }
 
-    Ok(())
+    let mut dirs: Vec<PathBuf> = test_files
+        .iter()
+        .filter_map(|file| file.parent().map(PathBuf::from))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
 }
@@ -0,0 +1,441 @@
+use crate::bot::{self, TestBot};
+use crate::interrupt;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+/// Largest region that can be recorded
+const MAX_RECORD_VOLUME: i64 = 32 * 32 * 32;
+
+/// How often collected block updates are taken from the bot and printed
+const DRAIN_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long to wait for the server to send the game time before recording
+const GAME_TIME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Block ID and properties parsed from the bot's block state string
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordedBlock {
+    id: String,
+    properties: Vec<(String, String)>,
+}
+
+impl RecordedBlock {
+    /// Parse azalea's debug representation, e.g.
+    /// `BlockState(id: 5, OakFence { east: False, waterlogged: False })`
+    fn parse(state: &str) -> Self {
+        let inner = state
+            .strip_prefix("BlockState(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|rest| rest.split_once(", ").map(|(_, block)| block))
+            .unwrap_or(state)
+            .trim();
+
+        let (name, properties) = match inner.split_once(" {") {
+            Some((name, properties)) => (name, properties.trim_end_matches('}')),
+            None => (inner, ""),
+        };
+
+        let properties = properties
+            .split(", ")
+            .filter_map(|property| property.split_once(": "))
+            .map(|(key, value)| {
+                (
+                    key.trim().to_string(),
+                    snake_case(value.trim().trim_start_matches('_')),
+                )
+            })
+            .collect();
+
+        Self {
+            id: snake_case(name.trim()),
+            properties,
+        }
+    }
+
+    fn property(&self, key: &str) -> Option<&String> {
+        self.properties
+            .iter()
+            .find(|(property, _)| property == key)
+            .map(|(_, value)| value)
+    }
+
+    fn is_air(&self) -> bool {
+        matches!(self.id.as_str(), "air" | "cave_air" | "void_air")
+    }
+
+    /// `minecraft:id` without properties
+    fn name(&self) -> String {
+        format!("minecraft:{}", self.id)
+    }
+
+    /// `minecraft:id[key=value,...]`, as accepted by `setblock` and `fill`
+    fn block_string(&self) -> String {
+        if self.properties.is_empty() {
+            return self.name();
+        }
+        let properties: Vec<String> = self
+            .properties
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        format!("{}[{}]", self.name(), properties.join(","))
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// One block change seen while recording, in local coordinates
+struct Change {
+    tick: u32,
+    pos: [i32; 3],
+    old: RecordedBlock,
+    new: RecordedBlock,
+}
+
+impl Change {
+    /// Whether the change is something the player did rather than its effect
+    fn is_build_step(&self) -> bool {
+        self.old.id != self.new.id || is_interaction(&self.old, &self.new)
+    }
+}
+
+/// Whether a property change is a player using the block. Buttons are left out: placed
+/// pressed, they would never pop back out.
+fn is_interaction(old: &RecordedBlock, new: &RecordedBlock) -> bool {
+    let changed = |key: &str| old.property(key) != new.property(key);
+    let id = new.id.as_str();
+    match id {
+        "lever" => changed("powered"),
+        "repeater" => changed("delay"),
+        "comparator" => changed("mode"),
+        "note_block" => changed("note"),
+        "daylight_detector" => changed("inverted"),
+        // Doors opened by redstone change `powered` in the same update
+        _ if id.ends_with("_door") || id.ends_with("_trapdoor") || id.ends_with("_fence_gate") => {
+            changed("open") && !changed("powered")
+        }
+        _ => false,
+    }
+}
+
+type Snapshot = HashMap<[i32; 3], RecordedBlock>;
+
+/// Read every block of the region, keyed by local position
+async fn snapshot(bot: &TestBot, min: [i32; 3], max: [i32; 3]) -> Result<Snapshot> {
+    let mut blocks = HashMap::new();
+    for x in min[0]..=max[0] {
+        for y in min[1]..=max[1] {
+            for z in min[2]..=max[2] {
+                // Positions without chunk data are left out rather than recorded as air
+                if let Some(state) = bot.block_if_loaded([x, y, z]).await? {
+                    let local = [x - min[0], y - min[1], z - min[2]];
+                    blocks.insert(local, RecordedBlock::parse(&state));
+                }
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+/// Watch a world region while a player builds in it, then write the result as a test spec
+///
+/// Changes are taken from the block updates the server sends, at the server tick they were
+/// sent in. Block type changes and blocks a player uses (levers, doors, repeater delays, ...)
+/// become `place`/`fill`/`remove` actions; other property changes (redstone power, fence
+/// connections, ...) become `assert_state` checks. The first change is at tick 1 and the
+/// ticks between changes are kept as they were.
+pub async fn record(bot: &TestBot, region: [[i32; 3]; 2], output: &Path, name: &str) -> Result<()> {
+    let min = [
+        region[0][0].min(region[1][0]),
+        region[0][1].min(region[1][1]),
+        region[0][2].min(region[1][2]),
+    ];
+    let max = [
+        region[0][0].max(region[1][0]),
+        region[0][1].max(region[1][1]),
+        region[0][2].max(region[1][2]),
+    ];
+    let size = [
+        max[0] - min[0] + 1,
+        max[1] - min[1] + 1,
+        max[2] - min[2] + 1,
+    ];
+    let volume = size.iter().map(|axis| *axis as i64).product::<i64>();
+    if volume > MAX_RECORD_VOLUME {
+        anyhow::bail!(
+            "Region is {} blocks, at most {} can be recorded",
+            volume,
+            MAX_RECORD_VOLUME
+        );
+    }
+
+    // Move the bot next to the region so it receives its block updates, in spectator so it
+    // does not fall into the region and record its own landing
+    bot.observe_from([min[0] + size[0] / 2, max[1] + 2, min[2] + size[2] / 2])
+        .await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // The server sends the game time every second, which block updates are stamped with
+    let deadline = tokio::time::Instant::now() + GAME_TIME_TIMEOUT;
+    while bot.game_time().is_none() {
+        if tokio::time::Instant::now() >= deadline {
+            anyhow::bail!("The server did not send the game time");
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    // Updates sent while the region is read are applied on top of it
+    bot.track_block_changes(true);
+    let initial = snapshot(bot, min, max).await?;
+    let mut current = initial.clone();
+    let mut changes = Vec::new();
+
+    println!(
+        "{} Recording {}x{}x{} region at [{}, {}, {}] - build in game, press Ctrl-C to stop\n",
+        "●".red().bold(),
+        size[0],
+        size[1],
+        size[2],
+        min[0],
+        min[1],
+        min[2]
+    );
+
+    // Tick 0 is reserved for blocks that already existed; the first change is tick 1
+    let mut start_tick = None;
+    let mut tick = 0;
+    let mut interval = tokio::time::interval(DRAIN_INTERVAL);
+    let mut stop = std::pin::pin!(interrupt::requested());
+
+    loop {
+        let stopping = tokio::select! {
+            _ = &mut stop => true,
+            _ = interval.tick() => false,
+        };
+
+        let mut seen: BTreeMap<u32, usize> = BTreeMap::new();
+        for update in bot.take_block_changes() {
+            let local = [
+                update.pos[0] - min[0],
+                update.pos[1] - min[1],
+                update.pos[2] - min[2],
+            ];
+            if (0..3).any(|axis| local[axis] < 0 || local[axis] >= size[axis]) {
+                continue;
+            }
+            let Some(server_tick) = update.tick else {
+                continue;
+            };
+            let new = RecordedBlock::parse(&update.block);
+            let Some(old) = current.insert(local, new.clone()) else {
+                continue;
+            };
+            if old == new {
+                continue;
+            }
+
+            let first = *start_tick.get_or_insert(server_tick);
+            tick = (server_tick.saturating_sub(first) + 1) as u32;
+            *seen.entry(tick).or_default() += 1;
+            changes.push(Change {
+                tick,
+                pos: local,
+                old,
+                new,
+            });
+        }
+        for (tick, count) in seen {
+            println!("  {} Tick {}: {} block change(s)", "→".blue(), tick, count);
+        }
+
+        if stopping {
+            break;
+        }
+    }
+    bot.track_block_changes(false);
+
+    let region_max = [size[0] - 1, size[1] - 1, size[2] - 1];
+    let spec = build_spec(name, region_max, &initial, &current, &changes, tick);
+    std::fs::write(output, serde_json::to_string_pretty(&spec)?)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!(
+        "\n{} Recorded {} change(s) over {} ticks to {}",
+        "✓".green(),
+        changes.len(),
+        tick,
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn build_spec(
+    name: &str,
+    region_max: [i32; 3],
+    initial: &Snapshot,
+    last: &Snapshot,
+    changes: &[Change],
+    last_tick: u32,
+) -> Value {
+    let mut timeline = Vec::new();
+
+    // Blocks that were there before recording started are placed at tick 0
+    let existing: Vec<([i32; 3], &RecordedBlock)> = initial
+        .iter()
+        .filter(|(_, block)| !block.is_air())
+        .map(|(pos, block)| (*pos, block))
+        .collect();
+    timeline.extend(placement_actions(0, existing));
+
+    // Type changes and blocks the player used are build steps, placed with their full state
+    let mut by_tick: BTreeMap<u32, Vec<([i32; 3], &RecordedBlock)>> = BTreeMap::new();
+    for change in changes.iter().filter(|change| change.is_build_step()) {
+        if change.new.is_air() {
+            timeline.push(json!({
+                "at": change.tick,
+                "do": "remove",
+                "pos": change.pos,
+            }));
+        } else {
+            by_tick
+                .entry(change.tick)
+                .or_default()
+                .push((change.pos, &change.new));
+        }
+    }
+    for (tick, placements) in by_tick {
+        timeline.extend(placement_actions(tick, placements));
+    }
+
+    // Other property changes are observed behaviour, merged per position and property
+    let mut states: BTreeMap<([i32; 3], String), Vec<(u32, String)>> = BTreeMap::new();
+    for change in changes.iter().filter(|change| !change.is_build_step()) {
+        for (key, value) in &change.new.properties {
+            if change.old.property(key) != Some(value) {
+                states
+                    .entry((change.pos, key.clone()))
+                    .or_default()
+                    .push((change.tick, value.clone()));
+            }
+        }
+    }
+    for ((pos, state), observed) in states {
+        let ticks: Vec<u32> = observed.iter().map(|(tick, _)| *tick).collect();
+        let values: Vec<&String> = observed.iter().map(|(_, value)| value).collect();
+        timeline.push(json!({
+            "at": if ticks.len() == 1 { json!(ticks[0]) } else { json!(ticks) },
+            "do": "assert_state",
+            "pos": pos,
+            "state": state,
+            "values": values,
+        }));
+    }
+
+    // Finally check the finished build
+    let mut final_blocks: Vec<([i32; 3], &RecordedBlock)> = last
+        .iter()
+        .filter(|(_, block)| !block.is_air())
+        .map(|(pos, block)| (*pos, block))
+        .collect();
+    final_blocks.sort_by_key(|(pos, _)| *pos);
+    if !final_blocks.is_empty() {
+        let checks: Vec<Value> = final_blocks
+            .iter()
+            .map(|(pos, block)| json!({ "pos": pos, "is": block.name() }))
+            .collect();
+        timeline.push(json!({
+            "at": last_tick + 1,
+            "do": "assert",
+            "checks": checks,
+        }));
+    }
+
+    timeline.sort_by_key(|entry| first_tick(&entry["at"]));
+
+    json!({
+        "flintVersion": "0.1",
+        "name": name,
+        "description": "Recorded with flintmc record",
+        "tags": ["recorded"],
+        "setup": {
+            "cleanup": {
+                "region": [[0, 0, 0], region_max]
+            }
+        },
+        "timeline": timeline,
+    })
+}
+
+fn first_tick(at: &Value) -> u64 {
+    match at {
+        Value::Array(ticks) => ticks.first().and_then(Value::as_u64).unwrap_or(0),
+        at => at.as_u64().unwrap_or(0),
+    }
+}
+
+/// `fill` for every block type that forms a complete cuboid, `place`/`place_each` for the rest
+fn placement_actions(tick: u32, mut placements: Vec<([i32; 3], &RecordedBlock)>) -> Vec<Value> {
+    placements.sort_by_key(|(pos, _)| *pos);
+
+    let mut by_block: BTreeMap<String, Vec<[i32; 3]>> = BTreeMap::new();
+    for (pos, block) in &placements {
+        by_block.entry(block.block_string()).or_default().push(*pos);
+    }
+
+    let mut actions = Vec::new();
+    let mut singles = Vec::new();
+    for (block, mut positions) in by_block {
+        positions.dedup();
+        match bot::cuboid(&positions) {
+            Some(region) if positions.len() > 1 => actions.push(json!({
+                "at": tick,
+                "do": "fill",
+                "region": region,
+                "with": block,
+            })),
+            _ => singles.extend(
+                positions
+                    .into_iter()
+                    .map(|pos| json!({ "pos": pos, "block": block })),
+            ),
+        }
+    }
+
+    match singles.len() {
+        0 => {}
+        1 => {
+            let single = singles.remove(0);
+            actions.push(json!({
+                "at": tick,
+                "do": "place",
+                "pos": single["pos"],
+                "block": single["block"],
+            }));
+        }
+        _ => actions.push(json!({
+            "at": tick,
+            "do": "place_each",
+            "blocks": singles,
+        })),
+    }
+
+    actions
+}
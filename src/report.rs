@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use flint_core::results::TestResult;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const DEFAULT_REPORT_PATH: &str = "flintmc-report.json";
+
+/// Past statuses kept per test in the JSON report
+const HISTORY_LENGTH: usize = 20;
+
+/// A test's result over every attempt of one run
+pub struct TestOutcome {
+    /// Result of the last attempt
+    pub result: TestResult,
+    /// Whether each attempt passed, first attempt first
+    pub attempts: Vec<bool>,
+    /// Listed under `quarantine` in the config: reported, but never fails the run
+    pub quarantined: bool,
+    /// The last attempt hit an infrastructure error instead of failing an assertion
+    pub errored: bool,
+}
+
+impl TestOutcome {
+    pub fn new(result: TestResult, errored: bool) -> Self {
+        Self {
+            attempts: vec![result.success],
+            result,
+            quarantined: false,
+            errored,
+        }
+    }
+
+    pub fn retry(&mut self, result: TestResult, errored: bool) {
+        self.attempts.push(result.success);
+        self.result = result;
+        self.errored = errored;
+    }
+
+    /// Failed at first but passed on a retry
+    pub fn is_flaky(&self) -> bool {
+        self.result.success && self.attempts.len() > 1
+    }
+
+    pub fn fails_run(&self) -> bool {
+        !self.result.success && !self.quarantined
+    }
+
+    /// Errored tests count towards the infrastructure exit code instead of failures
+    pub fn errors_run(&self) -> bool {
+        self.fails_run() && self.errored
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.is_flaky() {
+            "flaky"
+        } else if self.result.success {
+            "pass"
+        } else if self.errored {
+            "error"
+        } else {
+            "fail"
+        }
+    }
+}
+
+/// Print the PASS/FAIL table and totals for a run
+pub fn print_summary(outcomes: &[TestOutcome]) {
+    println!("\n{}", "═".repeat(60).dimmed());
+    println!("{}", "Test Summary".cyan().bold());
+    println!("{}", "═".repeat(60).dimmed());
+
+    let total_passed = outcomes
+        .iter()
+        .filter(|o| o.result.success && !o.is_flaky())
+        .count();
+    let total_flaky = outcomes.iter().filter(|o| o.is_flaky()).count();
+    let total_errored = outcomes.iter().filter(|o| o.status() == "error").count();
+    let total_failed = outcomes.len() - total_passed - total_flaky - total_errored;
+
+    for outcome in outcomes {
+        let status = match outcome.status() {
+            "flaky" => "FLAKY".yellow().bold(),
+            "pass" => "PASS".green().bold(),
+            "error" => "ERROR".magenta().bold(),
+            _ => "FAIL".red().bold(),
+        };
+        let mut notes = Vec::new();
+        if outcome.attempts.len() > 1 {
+            notes.push(format!("{} attempts", outcome.attempts.len()));
+        }
+        if outcome.quarantined {
+            notes.push("quarantined".to_string());
+        }
+        if notes.is_empty() {
+            println!("  [{}] {}", status, outcome.result.test_name);
+        } else {
+            println!(
+                "  [{}] {} {}",
+                status,
+                outcome.result.test_name,
+                format!("({})", notes.join(", ")).dimmed()
+            );
+        }
+    }
+
+    println!(
+        "\n{} tests run: {} passed, {} flaky, {} failed, {} errored\n",
+        outcomes.len(),
+        total_passed.to_string().green(),
+        total_flaky.to_string().yellow(),
+        total_failed.to_string().red(),
+        total_errored.to_string().magenta()
+    );
+}
+
+/// Statuses of earlier runs by test name, read from a previous report at `path`
+fn read_history(path: &Path) -> HashMap<String, Vec<Value>> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    let Ok(previous) = serde_json::from_str::<Value>(&text) else {
+        return HashMap::new();
+    };
+
+    previous["tests"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|test| {
+            let name = test["name"].as_str()?;
+            let history = test["history"].as_array().cloned().unwrap_or_default();
+            Some((name.to_string(), history))
+        })
+        .collect()
+}
+
+/// Write a machine-readable summary of a run, extending the flakiness history of the
+/// report previously written to the same path
+pub fn write_json_report(path: &Path, outcomes: &[TestOutcome]) -> Result<()> {
+    let mut history = read_history(path);
+    let count = |status: &str| outcomes.iter().filter(|o| o.status() == status).count();
+
+    let tests: Vec<Value> = outcomes
+        .iter()
+        .map(|outcome| {
+            let mut test_history = history
+                .remove(&outcome.result.test_name)
+                .unwrap_or_default();
+            test_history.push(Value::from(outcome.status()));
+            let excess = test_history.len().saturating_sub(HISTORY_LENGTH);
+            test_history.drain(..excess);
+            let flaky_runs = test_history
+                .iter()
+                .filter(|status| status.as_str() == Some("flaky"))
+                .count();
+
+            serde_json::json!({
+                "name": outcome.result.test_name,
+                "status": outcome.status(),
+                "attempts": outcome
+                    .attempts
+                    .iter()
+                    .map(|passed| if *passed { "pass" } else { "fail" })
+                    .collect::<Vec<_>>(),
+                "quarantined": outcome.quarantined,
+                "history": test_history,
+                "flaky_runs": flaky_runs,
+            })
+        })
+        .collect();
+
+    let report = serde_json::json!({
+        "total": outcomes.len(),
+        "passed": count("pass"),
+        "flaky": count("flaky"),
+        "failed": count("fail"),
+        "errored": count("error"),
+        "tests": tests,
+    });
+
+    let json = serde_json::to_string_pretty(&report)?;
+    std::fs::write(path, json)
+        .with_context(|| format!("Failed to write report to {}", path.display()))?;
+
+    Ok(())
+}
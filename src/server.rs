@@ -0,0 +1,217 @@
+use crate::config::ManagedServerConfig;
+use crate::interrupt;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc;
+
+/// Flat, peaceful world without mobs, so nothing but the tests changes blocks
+const GENERATOR_SETTINGS: &str = r#"{"layers":[{"block":"minecraft:bedrock","height":1},{"block":"minecraft:stone","height":2},{"block":"minecraft:grass_block","height":1}],"biome":"minecraft:plains"}"#;
+
+/// Time allowed for the server to save and exit after `stop`
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A server jar started and owned by FlintMC for the duration of a run
+pub struct ManagedServer {
+    child: Child,
+    stdin: ChildStdin,
+    /// Console output lines, only read until the bot has been opped
+    lines: Option<mpsc::UnboundedReceiver<String>>,
+    dir: PathBuf,
+    port: u16,
+}
+
+impl ManagedServer {
+    /// Write the server configuration, start the jar and wait until it accepts players
+    pub async fn start(jar: &Path, config: &ManagedServerConfig) -> Result<Self> {
+        let jar = jar
+            .canonicalize()
+            .with_context(|| format!("Server jar {} not found", jar.display()))?;
+        let dir = config.dir.clone();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create server directory {}", dir.display()))?;
+
+        let port = match config.port {
+            Some(port) => port,
+            None => free_port()?,
+        };
+        std::fs::write(dir.join("eula.txt"), "eula=true\n")?;
+        std::fs::write(dir.join("server.properties"), server_properties(port))?;
+
+        println!(
+            "{} Starting {} in {} on port {}...",
+            "→".blue(),
+            jar.display(),
+            dir.display(),
+            port
+        );
+
+        let mut child = Command::new(&config.java)
+            .args(&config.jvm_args)
+            .arg("-jar")
+            .arg(&jar)
+            .arg("nogui")
+            .current_dir(&dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", config.java))?;
+
+        let stdin = child.stdin.take().context("Server stdin is not piped")?;
+        let stdout = child.stdout.take().context("Server stdout is not piped")?;
+
+        // Forward console output to the log, and to `lines` while anyone listens
+        let (line_tx, line_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = reader.next_line().await {
+                tracing::debug!(target: "flintmc::server", "{}", line);
+                let _ = line_tx.send(line);
+            }
+        });
+
+        let mut server = Self {
+            child,
+            stdin,
+            lines: Some(line_rx),
+            dir,
+            port,
+        };
+
+        server
+            .wait_for_line(Duration::from_secs(config.startup_secs), |line| {
+                line.contains("Done (")
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Server did not finish starting (see {})",
+                    server.log_path().display()
+                )
+            })?;
+        println!("{} Server ready\n", "✓".green());
+
+        Ok(server)
+    }
+
+    /// Address the bot connects to
+    pub fn address(&self) -> String {
+        format!("127.0.0.1:{}", self.port)
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.dir.join("logs").join("latest.log")
+    }
+
+    /// Make the bot an operator through the console and wait for confirmation
+    pub async fn op(&mut self, username: &str) -> Result<()> {
+        self.console(&format!("op {}", username)).await?;
+        let confirmation = format!("Made {} a server operator", username);
+        self.wait_for_line(Duration::from_secs(10), |line| {
+            line.contains(&confirmation) || line.contains("Nothing changed")
+        })
+        .await
+        .with_context(|| format!("Server did not confirm op for {}", username))?;
+
+        // No more console output is needed; stop buffering it
+        self.lines = None;
+        Ok(())
+    }
+
+    /// Send a line to the server console
+    pub async fn console(&mut self, command: &str) -> Result<()> {
+        self.stdin
+            .write_all(format!("{}\n", command).as_bytes())
+            .await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Save the world and shut the server down, killing it if it does not exit in time
+    pub async fn stop(mut self) -> Result<()> {
+        println!("{} Stopping server...", "→".blue());
+        if self.console("stop").await.is_ok()
+            && let Ok(status) = tokio::time::timeout(STOP_TIMEOUT, self.child.wait()).await
+        {
+            status?;
+        } else {
+            self.child.kill().await?;
+        }
+        println!(
+            "{} Server stopped, log kept at {}",
+            "✓".green(),
+            self.log_path().display()
+        );
+        Ok(())
+    }
+
+    async fn wait_for_line(
+        &mut self,
+        timeout: Duration,
+        matches: impl Fn(&str) -> bool,
+    ) -> Result<()> {
+        let lines = self
+            .lines
+            .as_mut()
+            .context("Server output is no longer read")?;
+        let found = tokio::time::timeout(timeout, async {
+            tokio::select! {
+                found = async {
+                    while let Some(line) = lines.recv().await {
+                        if matches(&line) {
+                            return Some(true);
+                        }
+                    }
+                    // Output closed: the server exited
+                    Some(false)
+                } => found,
+                _ = interrupt::requested() => None,
+            }
+        })
+        .await;
+
+        match found {
+            Ok(Some(true)) => Ok(()),
+            Ok(Some(false)) => anyhow::bail!("Server exited"),
+            Ok(None) => anyhow::bail!("Interrupted"),
+            Err(_) => anyhow::bail!("Timed out after {}s", timeout.as_secs()),
+        }
+    }
+}
+
+/// Ask the OS for a port nothing is listening on
+fn free_port() -> Result<u16> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+fn server_properties(port: u16) -> String {
+    [
+        "# Generated by flintmc, overwritten on every run".to_string(),
+        "online-mode=false".to_string(),
+        "server-ip=127.0.0.1".to_string(),
+        format!("server-port={}", port),
+        "level-type=flat".to_string(),
+        format!("generator-settings={}", GENERATOR_SETTINGS),
+        "gamemode=creative".to_string(),
+        "difficulty=peaceful".to_string(),
+        "spawn-monsters=false".to_string(),
+        "spawn-animals=false".to_string(),
+        "spawn-npcs=false".to_string(),
+        "spawn-protection=0".to_string(),
+        "pvp=false".to_string(),
+        "max-players=10".to_string(),
+        "view-distance=10".to_string(),
+        "simulation-distance=10".to_string(),
+        "enable-command-block=true".to_string(),
+        "motd=FlintMC test server".to_string(),
+    ]
+    .join("\n")
+        + "\n"
+}
@@ -0,0 +1,156 @@
+use crate::spec::SpecExtensions;
+use flint_core::test_spec::TestSpec;
+use flint_core::timeline::TimelineAggregate;
+
+/// Tests assigned to one server, in their original order
+#[derive(Default)]
+pub struct Shard {
+    pub tests: Vec<TestSpec>,
+    pub extensions: Vec<SpecExtensions>,
+    /// Estimated length of the shard's run in ticks, see `Load`
+    pub ticks: u64,
+}
+
+/// Rough cost of preparing, cleaning and checking one test's region, in ticks of run time
+const TEST_OVERHEAD_TICKS: u64 = 20;
+
+/// Ticks a shard takes: tests sharing waves run side by side, so only the longest counts,
+/// while isolated tests run one after another. Every test adds its setup and cleanup.
+#[derive(Debug, Clone, Copy, Default)]
+struct Load {
+    longest_shared: u64,
+    isolated: u64,
+    tests: u64,
+}
+
+impl Load {
+    fn ticks(&self) -> u64 {
+        self.longest_shared + self.isolated + self.tests * TEST_OVERHEAD_TICKS
+    }
+
+    fn with(mut self, ticks: u64, isolated: bool) -> Self {
+        if isolated {
+            self.isolated += ticks;
+        } else {
+            self.longest_shared = self.longest_shared.max(ticks);
+        }
+        self.tests += 1;
+        self
+    }
+}
+
+/// Estimated length of a test in ticks: its last timeline action
+pub fn estimated_ticks(test: &TestSpec, extensions: &SpecExtensions) -> u32 {
+    TimelineAggregate::from_tests(&[(test.clone(), [0, 0, 0])])
+        .max_tick
+        .max(extensions.max_tick())
+}
+
+/// Split tests into `count` shards of similar estimated length, longest tests first
+pub fn distribute(tests: Vec<(TestSpec, SpecExtensions)>, count: usize) -> Vec<Shard> {
+    let mut estimated: Vec<(usize, TestSpec, SpecExtensions, u32)> = tests
+        .into_iter()
+        .enumerate()
+        .map(|(index, (test, extensions))| {
+            let ticks = estimated_ticks(&test, &extensions);
+            (index, test, extensions, ticks)
+        })
+        .collect();
+    estimated.sort_by_key(|(index, _, _, ticks)| (std::cmp::Reverse(*ticks), *index));
+
+    let mut assigned: Vec<Vec<(usize, TestSpec, SpecExtensions)>> =
+        (0..count.max(1)).map(|_| Vec::new()).collect();
+    let mut loads = vec![Load::default(); assigned.len()];
+    for (index, test, extensions, ticks) in estimated {
+        let ticks = ticks as u64;
+        let isolated = extensions.needs_isolation();
+        // Ties go to the shard with fewer tests
+        let shard = (0..loads.len())
+            .min_by_key(|shard| {
+                (
+                    loads[*shard].with(ticks, isolated).ticks(),
+                    assigned[*shard].len(),
+                )
+            })
+            .unwrap_or(0);
+        loads[shard] = loads[shard].with(ticks, isolated);
+        assigned[shard].push((index, test, extensions));
+    }
+
+    assigned
+        .into_iter()
+        .zip(loads)
+        .map(|(mut tests, load)| {
+            tests.sort_by_key(|(index, _, _)| *index);
+            let (tests, extensions) = tests
+                .into_iter()
+                .map(|(_, test, extensions)| (test, extensions))
+                .unzip();
+            Shard {
+                tests,
+                extensions,
+                ticks: load.ticks(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(name: &str, last_tick: u32) -> (TestSpec, SpecExtensions) {
+        let spec = serde_json::from_value(serde_json::json!({
+            "flintVersion": "0.1",
+            "name": name,
+            "setup": { "cleanup": { "region": [[0, 0, 0], [3, 3, 3]] } },
+            "timeline": [
+                { "at": 0, "do": "place", "pos": [0, 1, 0], "block": "minecraft:stone" },
+                {
+                    "at": last_tick,
+                    "do": "assert",
+                    "checks": [{ "pos": [0, 1, 0], "is": "minecraft:stone" }]
+                }
+            ]
+        }))
+        .unwrap();
+        (spec, SpecExtensions::default())
+    }
+
+    fn names(shard: &Shard) -> Vec<&str> {
+        shard.tests.iter().map(|test| test.name.as_str()).collect()
+    }
+
+    #[test]
+    fn equal_tests_split_evenly() {
+        let tests = (0..8)
+            .map(|index| test(&format!("t{}", index), 10))
+            .collect();
+        let shards = distribute(tests, 4);
+        assert_eq!(shards.len(), 4);
+        for shard in &shards {
+            assert_eq!(shard.tests.len(), 2);
+        }
+    }
+
+    #[test]
+    fn short_tests_do_not_all_follow_the_longest() {
+        let mut tests = vec![test("long", 200)];
+        tests.extend((0..12).map(|index| test(&format!("short{}", index), 5)));
+        let shards = distribute(tests, 2);
+        assert!(names(&shards[0]).contains(&"long"));
+        assert!(shards[0].tests.len() > 1);
+        assert!(shards[1].tests.len() > 1);
+    }
+
+    #[test]
+    fn shards_keep_the_original_order() {
+        let tests = vec![test("a", 5), test("b", 50), test("c", 5), test("d", 50)];
+        for shard in distribute(tests, 2) {
+            let names = names(&shard);
+            let mut sorted = names.clone();
+            sorted.sort();
+            assert_eq!(names, sorted);
+        }
+    }
+}
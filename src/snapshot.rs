@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Golden block states of a local region, stored next to the test as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionSnapshot {
+    pub region: [[i32; 3]; 2],
+    /// Block state by local position, keyed as "x,y,z"
+    pub blocks: BTreeMap<String, String>,
+}
+
+/// One position whose block differs from the snapshot
+pub struct BlockDiff {
+    pub pos: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl RegionSnapshot {
+    pub fn key(pos: [i32; 3]) -> String {
+        format!("{},{},{}", pos[0], pos[1], pos[2])
+    }
+
+    /// Every local position in the region
+    pub fn positions(region: [[i32; 3]; 2]) -> Vec<[i32; 3]> {
+        let min = [
+            region[0][0].min(region[1][0]),
+            region[0][1].min(region[1][1]),
+            region[0][2].min(region[1][2]),
+        ];
+        let max = [
+            region[0][0].max(region[1][0]),
+            region[0][1].max(region[1][1]),
+            region[0][2].max(region[1][2]),
+        ];
+
+        let mut positions = Vec::new();
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    positions.push([x, y, z]);
+                }
+            }
+        }
+        positions
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+        let snapshot = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse snapshot {}", path.display()))?;
+        Ok(Some(snapshot))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write snapshot {}", path.display()))
+    }
+
+    /// Positions where `actual` differs from this snapshot, in position order
+    pub fn diff(&self, actual: &RegionSnapshot) -> Vec<BlockDiff> {
+        let mut keys: Vec<&String> = self.blocks.keys().chain(actual.blocks.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        keys.into_iter()
+            .filter_map(|key| {
+                let expected = self.blocks.get(key);
+                let found = actual.blocks.get(key);
+                (expected != found).then(|| BlockDiff {
+                    pos: key.clone(),
+                    expected: expected.cloned(),
+                    actual: found.cloned(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Snapshot file for a test: `<dir>/<test file stem>.<name>.snap.json`
+pub fn snapshot_path(test_file: &Path, name: &str) -> PathBuf {
+    let stem = test_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    test_file.with_file_name(format!("{}.{}.snap.json", stem, name))
+}
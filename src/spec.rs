@@ -0,0 +1,300 @@
+//! FlintMC-specific extensions to flint-core test specs
+//!
+//! Timeline entries whose `do` is one of `EXTENSION_ACTIONS`, the `exclusive` flag,
+//! `dimension` and `setup.world` are taken out of the spec before flint-core parses it, and
+//! handled by FlintMC itself.
+
+use crate::error::FlintError;
+use crate::structure::{Mirror, Rotation, Structure};
+use crate::world::{self, WorldSetup};
+use anyhow::{Context, Result};
+use flint_core::test_spec::TestSpec;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Timeline actions that flint-core does not know about
+const EXTENSION_ACTIONS: &[&str] = &["assert_snapshot", "place_structure", "assert_mspt"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "do", rename_all = "snake_case")]
+pub enum ExtensionAction {
+    /// Compare every block in a local region against a golden snapshot file
+    AssertSnapshot {
+        region: [[i32; 3]; 2],
+        /// Distinguishes several snapshots of one test, defaults to `tick_<T>`
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Place a structure (`.nbt`) or schematic (`.schem`) file at a local position
+    PlaceStructure {
+        /// Path relative to the spec file
+        #[serde(default)]
+        file: Option<PathBuf>,
+        /// Structure id on the server (e.g. `minecraft:my_machine`), placed with `/place template`
+        #[serde(default)]
+        template: Option<String>,
+        #[serde(default)]
+        pos: [i32; 3],
+        #[serde(default)]
+        rotation: Rotation,
+        #[serde(default)]
+        mirror: Mirror,
+        /// Contents of `file`, read when the spec is loaded
+        #[serde(skip)]
+        structure: Option<Arc<Structure>>,
+    },
+    /// Sprint extra ticks and fail if the server's ms per tick exceeds `max`
+    ///
+    /// The measured ticks come on top of the timeline, so it must be the last entry, and the
+    /// test always runs alone.
+    AssertMspt { ticks: u32, max: f64 },
+}
+
+impl ExtensionAction {
+    /// Name as written in test specs
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExtensionAction::AssertSnapshot { .. } => "assert_snapshot",
+            ExtensionAction::PlaceStructure { .. } => "place_structure",
+            ExtensionAction::AssertMspt { .. } => "assert_mspt",
+        }
+    }
+
+    /// Local region the action places blocks in, as far as it is known: a template's size
+    /// is only known to the server
+    pub fn written_region(&self) -> Option<[[i32; 3]; 2]> {
+        if !matches!(self, ExtensionAction::PlaceStructure { .. }) {
+            return None;
+        }
+        match self.positions()[..] {
+            [(_, first), (_, second)] => Some([first, second]),
+            [(_, pos)] => Some([pos, pos]),
+            _ => None,
+        }
+    }
+
+    pub fn positions(&self) -> Vec<(&'static str, [i32; 3])> {
+        match self {
+            ExtensionAction::AssertSnapshot { region, .. } => {
+                vec![
+                    ("assert_snapshot", region[0]),
+                    ("assert_snapshot", region[1]),
+                ]
+            }
+            ExtensionAction::PlaceStructure {
+                pos,
+                rotation,
+                mirror,
+                structure,
+                ..
+            } => match structure {
+                Some(structure) => structure
+                    .placed_bounds(*rotation, *mirror)
+                    .iter()
+                    .map(|corner| {
+                        (
+                            "place_structure",
+                            [pos[0] + corner[0], pos[1] + corner[1], pos[2] + corner[2]],
+                        )
+                    })
+                    .collect(),
+                None => vec![("place_structure", *pos)],
+            },
+            ExtensionAction::AssertMspt { .. } => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtensionEntry {
+    pub ticks: Vec<u32>,
+    pub action: ExtensionAction,
+}
+
+/// Everything FlintMC reads from a spec on top of flint-core's `TestSpec`
+#[derive(Debug, Clone, Default)]
+pub struct SpecExtensions {
+    /// File the spec was loaded from
+    pub source: PathBuf,
+    pub actions: Vec<ExtensionEntry>,
+    /// Whether the test depends on server-global state and must not share its wave
+    pub exclusive: bool,
+    /// Global state applied before the test and restored afterwards
+    pub world: Option<WorldSetup>,
+    /// Namespaced id of the dimension the test runs in, the overworld when unset
+    pub dimension: Option<String>,
+}
+
+impl SpecExtensions {
+    /// Last tick of any extension action
+    pub fn max_tick(&self) -> u32 {
+        self.actions
+            .iter()
+            .flat_map(|entry| entry.ticks.iter().copied())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn dimension(&self) -> &str {
+        self.dimension.as_deref().unwrap_or(world::OVERWORLD)
+    }
+
+    /// Whether the test must run without other tests in its wave
+    pub fn needs_isolation(&self) -> bool {
+        self.exclusive
+            || self.world.is_some()
+            || self
+                .actions
+                .iter()
+                .any(|entry| matches!(entry.action, ExtensionAction::AssertMspt { .. }))
+    }
+}
+
+/// Load a test spec along with its FlintMC extensions
+pub fn load(path: &Path) -> Result<(TestSpec, SpecExtensions)> {
+    load_unchecked(path).map_err(|e| {
+        FlintError::InvalidSpec {
+            path: path.to_path_buf(),
+            message: format!("{:#}", e),
+        }
+        .into()
+    })
+}
+
+fn load_unchecked(path: &Path) -> Result<(TestSpec, SpecExtensions)> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut json: Value = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut extensions = SpecExtensions {
+        source: path.to_path_buf(),
+        ..Default::default()
+    };
+
+    // Keys flint-core does not know about, removed even when they hold the default
+    if let Some(exclusive) = json
+        .as_object_mut()
+        .and_then(|spec| spec.remove("exclusive"))
+    {
+        extensions.exclusive = serde_json::from_value(exclusive)
+            .with_context(|| format!("Invalid exclusive in {}", path.display()))?;
+    }
+    if let Some(dimension) = json
+        .as_object_mut()
+        .and_then(|spec| spec.remove("dimension"))
+    {
+        let dimension: String = serde_json::from_value(dimension)
+            .with_context(|| format!("Invalid dimension in {}", path.display()))?;
+        if dimension.is_empty() || dimension.contains(char::is_whitespace) {
+            anyhow::bail!("Invalid dimension \"{}\" in {}", dimension, path.display());
+        }
+        extensions.dimension = Some(world::dimension_id(&dimension));
+    }
+    if let Some(world) = json
+        .get_mut("setup")
+        .and_then(Value::as_object_mut)
+        .and_then(|setup| setup.remove("world"))
+    {
+        extensions.world = Some(
+            serde_json::from_value(world)
+                .with_context(|| format!("Invalid setup.world in {}", path.display()))?,
+        );
+    }
+
+    let mut core_last = None;
+    if let Some(timeline) = json.get_mut("timeline").and_then(Value::as_array_mut) {
+        let (extension_entries, core_entries): (Vec<Value>, Vec<Value>) =
+            timeline.drain(..).partition(|entry| {
+                entry["do"]
+                    .as_str()
+                    .is_some_and(|action| EXTENSION_ACTIONS.contains(&action))
+            });
+        core_last = core_entries.iter().flat_map(entry_ticks).max();
+        *timeline = core_entries;
+
+        for entry in extension_entries {
+            // flint-core rejects its own entries without a tick; these would silently never run
+            let ticks = entry_ticks(&entry);
+            if ticks.is_empty() {
+                anyhow::bail!(
+                    "{} needs a tick or list of ticks in \"at\" in {}",
+                    entry["do"],
+                    path.display()
+                );
+            }
+            let mut action: ExtensionAction = serde_json::from_value(entry.clone())
+                .with_context(|| format!("Invalid {} in {}", entry["do"], path.display()))?;
+            if let ExtensionAction::AssertMspt { ticks, max } = &action
+                && (*ticks == 0 || *max <= 0.0)
+            {
+                anyhow::bail!(
+                    "assert_mspt needs positive ticks and max in {}",
+                    path.display()
+                );
+            }
+            if let ExtensionAction::PlaceStructure {
+                file,
+                template,
+                structure,
+                ..
+            } = &mut action
+            {
+                if let Some(file) = file {
+                    *file = path.parent().unwrap_or(Path::new(".")).join(&file);
+                    *structure = Some(Arc::new(Structure::load(file)?));
+                } else if template.is_none() {
+                    anyhow::bail!(
+                        "place_structure needs a file or template in {}",
+                        path.display()
+                    );
+                }
+            }
+            extensions.actions.push(ExtensionEntry {
+                ticks: ticks.into_iter().map(|tick| tick as u32).collect(),
+                action,
+            });
+        }
+    }
+
+    // The measured sprint moves the server on, so nothing may be left to run after it.
+    // flint-core actions of the same tick run before extension actions.
+    for (index, entry) in extensions.actions.iter().enumerate() {
+        if !matches!(entry.action, ExtensionAction::AssertMspt { .. }) {
+            continue;
+        }
+        let tick = match entry.ticks[..] {
+            [tick] => tick,
+            _ => anyhow::bail!("assert_mspt must be at a single tick in {}", path.display()),
+        };
+        let later_core = core_last.is_some_and(|last| last > tick as u64);
+        let later_extension = extensions
+            .actions
+            .iter()
+            .enumerate()
+            .any(|(other, entry)| other != index && entry.ticks.iter().any(|t| *t >= tick));
+        if later_core || later_extension {
+            anyhow::bail!(
+                "assert_mspt at tick {} must come after every other timeline entry in {}",
+                tick,
+                path.display()
+            );
+        }
+    }
+
+    // flint-core gets the JSON already read, with the extensions taken out
+    let spec: TestSpec = serde_json::from_value(json)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    Ok((spec, extensions))
+}
+
+/// Ticks a timeline entry runs at, from a single `at` or a list
+fn entry_ticks(entry: &Value) -> Vec<u64> {
+    match &entry["at"] {
+        Value::Array(ticks) => ticks.iter().filter_map(Value::as_u64).collect(),
+        at => at.as_u64().into_iter().collect(),
+    }
+}
@@ -0,0 +1,581 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::Path;
+
+/// Rotation around the placement position, named like the `/place template` arguments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Rotation {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "clockwise_90")]
+    Clockwise90,
+    #[serde(rename = "180")]
+    Clockwise180,
+    #[serde(rename = "counterclockwise_90")]
+    Counterclockwise90,
+}
+
+impl Rotation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rotation::None => "none",
+            Rotation::Clockwise90 => "clockwise_90",
+            Rotation::Clockwise180 => "180",
+            Rotation::Counterclockwise90 => "counterclockwise_90",
+        }
+    }
+
+    fn quarter_turns(self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::Counterclockwise90 => 3,
+        }
+    }
+}
+
+/// Mirror applied before rotating, named like the `/place template` arguments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mirror {
+    #[default]
+    None,
+    /// Flips the Z axis
+    LeftRight,
+    /// Flips the X axis
+    FrontBack,
+}
+
+impl Mirror {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mirror::None => "none",
+            Mirror::LeftRight => "left_right",
+            Mirror::FrontBack => "front_back",
+        }
+    }
+}
+
+/// Blocks of a structure or schematic file, relative to its minimum corner
+#[derive(Debug, Clone)]
+pub struct Structure {
+    pub size: [i32; 3],
+    /// Position and `minecraft:id[key=value,...]` of every non-air block
+    pub blocks: Vec<([i32; 3], String)>,
+}
+
+impl Structure {
+    /// Load a vanilla structure (`.nbt`) or Sponge schematic (`.schem`), gzipped or not
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read(path)
+            .with_context(|| format!("Failed to read structure {}", path.display()))?;
+        let bytes = if raw.starts_with(&[0x1f, 0x8b]) {
+            let mut bytes = Vec::new();
+            GzDecoder::new(raw.as_slice())
+                .read_to_end(&mut bytes)
+                .with_context(|| format!("Failed to decompress {}", path.display()))?;
+            bytes
+        } else {
+            raw
+        };
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        match extension {
+            "nbt" => parse_structure(&bytes),
+            "schem" => parse_schematic(&bytes),
+            other => anyhow::bail!(
+                "Unsupported structure format '.{}' (use .nbt or .schem)",
+                other
+            ),
+        }
+        .with_context(|| format!("Failed to parse structure {}", path.display()))
+    }
+
+    /// Blocks as they end up relative to the placement position, using the same transform
+    /// as `/place template`: mirror first, then rotate around the origin
+    pub fn placed(&self, rotation: Rotation, mirror: Mirror) -> Vec<([i32; 3], String)> {
+        self.blocks
+            .iter()
+            .map(|(pos, block)| {
+                (
+                    transform_pos(*pos, rotation, mirror),
+                    transform_block(block, rotation, mirror),
+                )
+            })
+            .collect()
+    }
+
+    /// Opposite corners of the placed structure, relative to the placement position
+    pub fn placed_bounds(&self, rotation: Rotation, mirror: Mirror) -> [[i32; 3]; 2] {
+        let far = [self.size[0] - 1, self.size[1] - 1, self.size[2] - 1];
+        [
+            transform_pos([0, 0, 0], rotation, mirror),
+            transform_pos(far, rotation, mirror),
+        ]
+    }
+}
+
+#[derive(Deserialize)]
+struct StructureFile {
+    size: Vec<i32>,
+    #[serde(default)]
+    palette: Vec<StructurePaletteEntry>,
+    /// Structures with random variants (shipwrecks) have several palettes; the first is used
+    #[serde(default)]
+    palettes: Vec<Vec<StructurePaletteEntry>>,
+    blocks: Vec<StructureBlock>,
+}
+
+#[derive(Deserialize)]
+struct StructurePaletteEntry {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Properties", default)]
+    properties: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct StructureBlock {
+    pos: Vec<i32>,
+    state: i32,
+    /// Block entity data, which `setblock`s from the file cannot carry over
+    #[serde(default)]
+    nbt: Option<fastnbt::Value>,
+}
+
+fn parse_structure(bytes: &[u8]) -> Result<Structure> {
+    let file: StructureFile = fastnbt::from_bytes(bytes)?;
+    let palette = if file.palette.is_empty() {
+        file.palettes.into_iter().next().unwrap_or_default()
+    } else {
+        file.palette
+    };
+    let [x, y, z] = file.size[..] else {
+        anyhow::bail!("Structure size must have 3 components");
+    };
+
+    let mut blocks = Vec::new();
+    for block in file.blocks {
+        let [bx, by, bz] = block.pos[..] else {
+            anyhow::bail!("Block position must have 3 components");
+        };
+        if block.nbt.is_some() {
+            anyhow::bail!(
+                "Block at [{}, {}, {}] has block entity data, which is lost when placing from a file; save the structure on the server and use template instead",
+                bx,
+                by,
+                bz
+            );
+        }
+        let entry = palette
+            .get(block.state as usize)
+            .with_context(|| format!("Block state {} is not in the palette", block.state))?;
+        if is_skipped(&entry.name) {
+            continue;
+        }
+        let properties: Vec<String> = entry
+            .properties
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        let block_string = if properties.is_empty() {
+            entry.name.clone()
+        } else {
+            format!("{}[{}]", entry.name, properties.join(","))
+        };
+        blocks.push(([bx, by, bz], block_string));
+    }
+
+    Ok(Structure {
+        size: [x, y, z],
+        blocks,
+    })
+}
+
+/// Sponge schematic version 2
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV2 {
+    width: i16,
+    height: i16,
+    length: i16,
+    palette: HashMap<String, i32>,
+    block_data: fastnbt::ByteArray,
+    #[serde(default)]
+    block_entities: Vec<fastnbt::Value>,
+}
+
+/// Sponge schematic version 3 nests everything below a `Schematic` compound
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV3Root {
+    schematic: SchematicV3,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV3 {
+    width: i16,
+    height: i16,
+    length: i16,
+    blocks: SchematicV3Blocks,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV3Blocks {
+    palette: HashMap<String, i32>,
+    data: fastnbt::ByteArray,
+    #[serde(default)]
+    block_entities: Vec<fastnbt::Value>,
+}
+
+fn parse_schematic(bytes: &[u8]) -> Result<Structure> {
+    let (size, palette, data, block_entities) = match fastnbt::from_bytes::<SchematicV3Root>(bytes)
+    {
+        Ok(root) => {
+            let schematic = root.schematic;
+            (
+                [schematic.width, schematic.height, schematic.length],
+                schematic.blocks.palette,
+                schematic.blocks.data,
+                schematic.blocks.block_entities.len(),
+            )
+        }
+        Err(_) => {
+            let schematic: SchematicV2 = fastnbt::from_bytes(bytes)?;
+            (
+                [schematic.width, schematic.height, schematic.length],
+                schematic.palette,
+                schematic.block_data,
+                schematic.block_entities.len(),
+            )
+        }
+    };
+    if block_entities > 0 {
+        anyhow::bail!(
+            "Schematic has {} block entities, whose data is lost when placing from a file; save the structure on the server and use template instead",
+            block_entities
+        );
+    }
+
+    // Dimensions are unsigned shorts
+    let size = size.map(|axis| axis as u16 as i32);
+    if size.contains(&0) {
+        anyhow::bail!(
+            "Schematic is {}x{}x{}, every dimension must be at least 1",
+            size[0],
+            size[1],
+            size[2]
+        );
+    }
+    let by_id: HashMap<i32, String> = palette.into_iter().map(|(block, id)| (id, block)).collect();
+
+    // Block data is a list of varint palette indices in x, z, y order
+    let data: Vec<u8> = data
+        .into_inner()
+        .into_iter()
+        .map(|byte| byte as u8)
+        .collect();
+    let mut blocks = Vec::new();
+    let mut index = 0;
+    let mut cursor = 0;
+    while cursor < data.len() {
+        let mut id = 0i32;
+        let mut shift = 0;
+        loop {
+            let byte = *data.get(cursor).context("Truncated block data")?;
+            cursor += 1;
+            id |= ((byte & 0x7f) as i32) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            anyhow::ensure!(shift < 32, "Invalid varint in block data");
+        }
+
+        let x = index % size[0];
+        let z = (index / size[0]) % size[2];
+        let y = index / (size[0] * size[2]);
+        index += 1;
+
+        let block = by_id
+            .get(&id)
+            .with_context(|| format!("Block id {} is not in the palette", id))?;
+        if !is_skipped(block) {
+            blocks.push(([x, y, z], block.clone()));
+        }
+    }
+    let volume = size.iter().map(|axis| *axis as i64).product::<i64>();
+    if index as i64 != volume {
+        anyhow::bail!(
+            "Schematic has {} blocks of data, but {}x{}x{} needs {}",
+            index,
+            size[0],
+            size[1],
+            size[2],
+            volume
+        );
+    }
+
+    Ok(Structure { size, blocks })
+}
+
+/// Air needs no command because test regions are cleaned first, structure voids are never placed
+fn is_skipped(block: &str) -> bool {
+    let name = block.split('[').next().unwrap_or(block);
+    matches!(
+        name.trim_start_matches("minecraft:"),
+        "air" | "cave_air" | "void_air" | "structure_void"
+    )
+}
+
+fn transform_pos(pos: [i32; 3], rotation: Rotation, mirror: Mirror) -> [i32; 3] {
+    let [mut x, y, mut z] = pos;
+    match mirror {
+        Mirror::None => {}
+        Mirror::LeftRight => z = -z,
+        Mirror::FrontBack => x = -x,
+    }
+    match rotation {
+        Rotation::None => [x, y, z],
+        Rotation::Clockwise90 => [-z, y, x],
+        Rotation::Clockwise180 => [-x, y, -z],
+        Rotation::Counterclockwise90 => [z, y, -x],
+    }
+}
+
+const HORIZONTAL: [&str; 4] = ["north", "east", "south", "west"];
+
+fn transform_direction(direction: &str, rotation: Rotation, mirror: Mirror) -> String {
+    let Some(index) = HORIZONTAL.iter().position(|d| *d == direction) else {
+        return direction.to_string();
+    };
+    let mirrored = match (mirror, index) {
+        (Mirror::LeftRight, 0 | 2) | (Mirror::FrontBack, 1 | 3) => (index + 2) % 4,
+        _ => index,
+    };
+    HORIZONTAL[(mirrored + rotation.quarter_turns() as usize) % 4].to_string()
+}
+
+/// Swap left and right, as a mirror does
+fn mirror_side(value: &str) -> String {
+    if let Some(prefix) = value.strip_suffix("left") {
+        format!("{}right", prefix)
+    } else if let Some(prefix) = value.strip_suffix("right") {
+        format!("{}left", prefix)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Rail shapes name the directions the rail connects to, e.g. `north_east` or
+/// `ascending_west`
+fn transform_rail_shape(shape: &str, rotation: Rotation, mirror: Mirror) -> String {
+    if let Some(direction) = shape.strip_prefix("ascending_") {
+        return format!(
+            "ascending_{}",
+            transform_direction(direction, rotation, mirror)
+        );
+    }
+    let Some((first, second)) = shape.split_once('_') else {
+        return shape.to_string();
+    };
+    let mut directions = [
+        transform_direction(first, rotation, mirror),
+        transform_direction(second, rotation, mirror),
+    ];
+    // Straight rails are named north_south and east_west, curves north or south first
+    directions.sort_by_key(|direction| match direction.as_str() {
+        "north" => 0,
+        "south" => 1,
+        "east" => 2,
+        _ => 3,
+    });
+    directions.join("_")
+}
+
+/// Jigsaw and crafter orientations pair a facing with a top direction, e.g. `up_east`
+fn transform_orientation(orientation: &str, rotation: Rotation, mirror: Mirror) -> String {
+    orientation
+        .split('_')
+        .map(|direction| transform_direction(direction, rotation, mirror))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Rotate and mirror the direction-like properties of a block state
+fn transform_block(block: &str, rotation: Rotation, mirror: Mirror) -> String {
+    if rotation == Rotation::None && mirror == Mirror::None {
+        return block.to_string();
+    }
+    let Some((name, properties)) = block.split_once('[') else {
+        return block.to_string();
+    };
+
+    let is_rail = name.ends_with("rail");
+    let properties: Vec<String> = properties
+        .trim_end_matches(']')
+        .split(',')
+        .filter_map(|property| property.split_once('='))
+        .map(|(key, value)| {
+            let (key, value) = match key {
+                "facing" => (
+                    key.to_string(),
+                    transform_direction(value, rotation, mirror),
+                ),
+                // Connections of fences, walls, panes and redstone wire move with the block
+                "north" | "east" | "south" | "west" => (
+                    transform_direction(key, rotation, mirror),
+                    value.to_string(),
+                ),
+                "shape" if is_rail => (
+                    key.to_string(),
+                    transform_rail_shape(value, rotation, mirror),
+                ),
+                "orientation" => (
+                    key.to_string(),
+                    transform_orientation(value, rotation, mirror),
+                ),
+                // Stair corners, door hinges and double chest halves are relative to the
+                // facing, which only a mirror turns around
+                "shape" | "hinge" | "type" if mirror != Mirror::None => {
+                    (key.to_string(), mirror_side(value))
+                }
+                "axis" if rotation.quarter_turns() % 2 == 1 => {
+                    let axis = match value {
+                        "x" => "z",
+                        "z" => "x",
+                        other => other,
+                    };
+                    (key.to_string(), axis.to_string())
+                }
+                // Signs, banners and skulls use 16 rotation steps
+                "rotation" => match value.parse::<u32>() {
+                    Ok(steps) => {
+                        let mirrored = match mirror {
+                            Mirror::None => steps,
+                            Mirror::LeftRight => (8 + 16 - steps) % 16,
+                            Mirror::FrontBack => (16 - steps) % 16,
+                        };
+                        let rotated = (mirrored + rotation.quarter_turns() * 4) % 16;
+                        (key.to_string(), rotated.to_string())
+                    }
+                    Err(_) => (key.to_string(), value.to_string()),
+                },
+                _ => (key.to_string(), value.to_string()),
+            };
+            format!("{}={}", key, value)
+        })
+        .collect();
+
+    format!("{}[{}]", name, properties.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotating_north_follows_the_rotation() {
+        let north = [0, 0, -1];
+        assert_eq!(
+            transform_pos(north, Rotation::Clockwise90, Mirror::None),
+            [1, 0, 0]
+        );
+        assert_eq!(
+            transform_pos(north, Rotation::Clockwise180, Mirror::None),
+            [0, 0, 1]
+        );
+        assert_eq!(
+            transform_pos(north, Rotation::Counterclockwise90, Mirror::None),
+            [-1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn four_quarter_turns_return_to_the_start() {
+        let mut pos = [3, 1, -2];
+        for _ in 0..4 {
+            pos = transform_pos(pos, Rotation::Clockwise90, Mirror::None);
+        }
+        assert_eq!(pos, [3, 1, -2]);
+    }
+
+    #[test]
+    fn mirrors_flip_one_axis() {
+        assert_eq!(
+            transform_pos([2, 5, 3], Rotation::None, Mirror::LeftRight),
+            [2, 5, -3]
+        );
+        assert_eq!(
+            transform_pos([2, 5, 3], Rotation::None, Mirror::FrontBack),
+            [-2, 5, 3]
+        );
+    }
+
+    #[test]
+    fn rotation_turns_facing_and_keeps_stair_shape() {
+        assert_eq!(
+            transform_block(
+                "minecraft:oak_stairs[facing=north,half=bottom,shape=inner_left]",
+                Rotation::Clockwise90,
+                Mirror::None
+            ),
+            "minecraft:oak_stairs[facing=east,half=bottom,shape=inner_left]"
+        );
+    }
+
+    #[test]
+    fn mirror_turns_facing_and_swaps_stair_shape() {
+        assert_eq!(
+            transform_block(
+                "minecraft:oak_stairs[facing=north,half=bottom,shape=inner_left]",
+                Rotation::None,
+                Mirror::LeftRight
+            ),
+            "minecraft:oak_stairs[facing=south,half=bottom,shape=inner_right]"
+        );
+    }
+
+    #[test]
+    fn rotation_moves_connections_rail_shapes_and_axes() {
+        assert_eq!(
+            transform_block(
+                "minecraft:oak_fence[east=true,north=false,south=false,west=false]",
+                Rotation::Clockwise90,
+                Mirror::None
+            ),
+            "minecraft:oak_fence[south=true,east=false,west=false,north=false]"
+        );
+        assert_eq!(
+            transform_block(
+                "minecraft:rail[shape=north_east]",
+                Rotation::Clockwise90,
+                Mirror::None
+            ),
+            "minecraft:rail[shape=south_east]"
+        );
+        assert_eq!(
+            transform_block(
+                "minecraft:oak_log[axis=x]",
+                Rotation::Clockwise90,
+                Mirror::None
+            ),
+            "minecraft:oak_log[axis=z]"
+        );
+    }
+
+    #[test]
+    fn blocks_without_properties_are_unchanged() {
+        assert_eq!(
+            transform_block("minecraft:stone", Rotation::Clockwise180, Mirror::FrontBack),
+            "minecraft:stone"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+/// Assertions listed in the summary, slowest first
+const SLOWEST_ASSERTIONS: usize = 10;
+
+/// One `/tick sprint` and the speed the server reported for it
+#[derive(Debug, Clone)]
+pub struct SprintTiming {
+    /// Tick the sprint started after
+    pub from_tick: u32,
+    pub ticks: u32,
+    /// Milliseconds per tick from the "Sprint completed" message, if it was received
+    pub mspt: Option<f64>,
+    pub wall: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestTiming {
+    pub test: String,
+    /// From the start of the timeline until the test's last action finished
+    pub wall: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssertionTiming {
+    pub test: String,
+    pub tick: u32,
+    pub action: &'static str,
+    /// Time spent reading and re-reading blocks until the assertion passed or gave up
+    pub duration: Duration,
+}
+
+/// Where the time of one or more runs went
+#[derive(Debug, Clone, Default)]
+pub struct RunTimings {
+    /// Cleaning test regions and freezing time before the timeline starts
+    pub setup: Duration,
+    /// Cleaning test regions after the timeline
+    pub cleanup: Duration,
+    pub sprints: Vec<SprintTiming>,
+    pub tests: Vec<TestTiming>,
+    pub assertions: Vec<AssertionTiming>,
+}
+
+impl RunTimings {
+    pub fn merge(&mut self, other: RunTimings) {
+        self.setup += other.setup;
+        self.cleanup += other.cleanup;
+        self.sprints.extend(other.sprints);
+        self.tests.extend(other.tests);
+        self.assertions.extend(other.assertions);
+    }
+
+    /// Total time spent in assertions, which is almost entirely block polling
+    pub fn polling(&self) -> Duration {
+        self.assertions
+            .iter()
+            .map(|assertion| assertion.duration)
+            .sum()
+    }
+
+    fn slowest_assertions(&self) -> Vec<&AssertionTiming> {
+        let mut assertions: Vec<&AssertionTiming> = self.assertions.iter().collect();
+        assertions.sort_by(|a, b| b.duration.cmp(&a.duration));
+        assertions.truncate(SLOWEST_ASSERTIONS);
+        assertions
+    }
+
+    /// Average ms per tick over all sprints that reported one, weighted by sprint length
+    fn average_mspt(&self) -> Option<f64> {
+        let (total, ticks) = self
+            .sprints
+            .iter()
+            .filter_map(|sprint| Some((sprint.mspt? * sprint.ticks as f64, sprint.ticks as f64)))
+            .fold((0.0, 0.0), |(total, ticks), (ms, t)| {
+                (total + ms, ticks + t)
+            });
+        (ticks > 0.0).then(|| total / ticks)
+    }
+
+    fn slowest_sprint(&self) -> Option<&SprintTiming> {
+        self.sprints
+            .iter()
+            .filter(|sprint| sprint.mspt.is_some())
+            .max_by(|a, b| {
+                a.mspt
+                    .partial_cmp(&b.mspt)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+
+    pub fn print_summary(&self) {
+        println!("{}", "═".repeat(60).dimmed());
+        println!("{}", "Timings".cyan().bold());
+        println!("{}", "═".repeat(60).dimmed());
+
+        println!("  Setup:   {}", format_duration(self.setup));
+        println!("  Cleanup: {}", format_duration(self.cleanup));
+        println!(
+            "  Polling: {} over {} assertions",
+            format_duration(self.polling()),
+            self.assertions.len()
+        );
+
+        let sprint_wall: Duration = self.sprints.iter().map(|sprint| sprint.wall).sum();
+        println!(
+            "  Sprints: {} over {} sprints",
+            format_duration(sprint_wall),
+            self.sprints.len()
+        );
+        if let Some(average) = self.average_mspt() {
+            println!("    average {:.2} ms per tick", average);
+        }
+        if let Some(sprint) = self.slowest_sprint() {
+            println!(
+                "    slowest {:.2} ms per tick, ticks {}-{}",
+                sprint.mspt.unwrap_or_default(),
+                sprint.from_tick + 1,
+                sprint.from_tick + sprint.ticks
+            );
+        }
+
+        if !self.tests.is_empty() {
+            println!("\n  {}", "Wall time per test:".bold());
+            let mut tests: Vec<&TestTiming> = self.tests.iter().collect();
+            tests.sort_by(|a, b| b.wall.cmp(&a.wall));
+            for test in tests {
+                println!("    {:>9}  {}", format_duration(test.wall), test.test);
+            }
+        }
+
+        let slowest = self.slowest_assertions();
+        if !slowest.is_empty() {
+            println!("\n  {}", "Slowest assertions:".bold());
+            for assertion in slowest {
+                println!(
+                    "    {:>9}  [{}] Tick {}: {}",
+                    format_duration(assertion.duration),
+                    assertion.test,
+                    assertion.tick,
+                    assertion.action
+                );
+            }
+        }
+        println!();
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let report = json!({
+            "setup_ms": millis(self.setup),
+            "cleanup_ms": millis(self.cleanup),
+            "polling_ms": millis(self.polling()),
+            "average_mspt": self.average_mspt(),
+            "sprints": self.sprints.iter().map(|sprint| json!({
+                "from_tick": sprint.from_tick,
+                "ticks": sprint.ticks,
+                "mspt": sprint.mspt,
+                "wall_ms": millis(sprint.wall),
+            })).collect::<Vec<_>>(),
+            "tests": self.tests.iter().map(|test| json!({
+                "name": test.test,
+                "wall_ms": millis(test.wall),
+            })).collect::<Vec<_>>(),
+            "assertions": self.assertions.iter().map(|assertion| json!({
+                "test": assertion.test,
+                "tick": assertion.tick,
+                "action": assertion.action,
+                "duration_ms": millis(assertion.duration),
+            })).collect::<Vec<_>>(),
+        });
+
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write timings to {}", path.display()))
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() >= 1 {
+        format!("{:.2}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
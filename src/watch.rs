@@ -0,0 +1,219 @@
+use crate::config::LayoutConfig;
+use crate::executor::TestExecutor;
+use crate::interrupt;
+use crate::layout;
+use crate::report::{self, TestOutcome};
+use crate::spec::{self, SpecExtensions};
+use anyhow::Result;
+use colored::Colorize;
+use flint_core::test_spec::TestSpec;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How often the watched paths are scanned for changes
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Tracks modification times of the test files below a set of paths
+struct SpecWatcher {
+    paths: Vec<PathBuf>,
+    recursive: bool,
+    mtimes: HashMap<PathBuf, SystemTime>,
+}
+
+impl SpecWatcher {
+    fn new(paths: &[PathBuf], recursive: bool) -> Self {
+        let mut watcher = Self {
+            paths: paths.to_vec(),
+            recursive,
+            mtimes: HashMap::new(),
+        };
+        watcher.mtimes = watcher.scan();
+        watcher
+    }
+
+    /// Files that were created or modified since the last call
+    fn changed(&mut self) -> Vec<PathBuf> {
+        let current = self.scan();
+        let mut changed: Vec<PathBuf> = current
+            .iter()
+            .filter(|(file, mtime)| self.mtimes.get(*file) != Some(*mtime))
+            .map(|(file, _)| file.clone())
+            .collect();
+        changed.sort();
+        self.mtimes = current;
+        changed
+    }
+
+    /// All test files currently present
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.mtimes.keys().cloned().collect();
+        files.sort();
+        files
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut mtimes = HashMap::new();
+        for path in &self.paths {
+            if path.is_dir() {
+                scan_dir(path, self.recursive, &mut mtimes);
+            } else if let Ok(mtime) = modified(path) {
+                mtimes.insert(path.clone(), mtime);
+            }
+        }
+        mtimes
+    }
+}
+
+fn scan_dir(dir: &Path, recursive: bool, mtimes: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                scan_dir(&path, recursive, mtimes);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "json")
+            && let Ok(mtime) = modified(&path)
+        {
+            mtimes.insert(path, mtime);
+        }
+    }
+}
+
+fn modified(path: &Path) -> std::io::Result<SystemTime> {
+    std::fs::metadata(path)?.modified()
+}
+
+/// Keep the bot connected and re-run changed tests, plus everything depending on them,
+/// whenever a JSON file below `paths` is saved
+pub async fn watch_tests(
+    executor: &mut TestExecutor,
+    paths: &[PathBuf],
+    recursive: bool,
+    tags: &[String],
+    break_after_setup: bool,
+    layout: &LayoutConfig,
+) -> Result<()> {
+    let mut watcher = SpecWatcher::new(paths, recursive);
+
+    println!(
+        "{} Watching {} test file(s) for changes (Ctrl-C to stop)\n",
+        "→".blue(),
+        watcher.files().len()
+    );
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = interrupt::requested() => return Ok(()),
+        }
+
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            continue;
+        }
+
+        // Load every known spec so dependents of the changed tests can be found
+        let mut specs = Vec::new();
+        let mut changed_names = HashSet::new();
+        for file in watcher.files() {
+            let is_changed = changed.contains(&file);
+            match spec::load(&file) {
+                Ok((test, extensions)) => {
+                    if !tags.is_empty() && !test.tags.iter().any(|tag| tags.contains(tag)) {
+                        continue;
+                    }
+                    if is_changed {
+                        println!("{} Changed: {}", "→".blue(), file.display());
+                        changed_names.insert(test.name.clone());
+                    }
+                    specs.push((test, extensions));
+                }
+                Err(e) if is_changed => {
+                    eprintln!(
+                        "{} Failed to load test {}: {}",
+                        "Error:".red().bold(),
+                        file.display(),
+                        e
+                    );
+                }
+                Err(_) => {}
+            }
+        }
+
+        let selected = with_dependents(&changed_names, &specs);
+        if selected.is_empty() {
+            continue;
+        }
+
+        let (selected_specs, extensions): (Vec<TestSpec>, Vec<SpecExtensions>) = specs
+            .into_iter()
+            .filter(|(test, _)| selected.contains(&test.name))
+            .unzip();
+        let offsets = match layout::place(&selected_specs, layout) {
+            Ok(offsets) => offsets,
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                continue;
+            }
+        };
+        let tests_with_offsets: Vec<(TestSpec, [i32; 3])> =
+            selected_specs.into_iter().zip(offsets).collect();
+
+        println!(
+            "{} Re-running {} test(s)\n",
+            "↻".cyan().bold(),
+            tests_with_offsets.len()
+        );
+
+        match executor
+            .run_tests_parallel(&tests_with_offsets, &extensions, break_after_setup)
+            .await
+        {
+            Ok(results) => {
+                let outcomes: Vec<TestOutcome> = results
+                    .into_iter()
+                    .map(|result| {
+                        let errored = executor.is_errored(&result.test_name);
+                        TestOutcome::new(result, errored)
+                    })
+                    .collect();
+                report::print_summary(&outcomes);
+            }
+            Err(e) => eprintln!("{} Run failed: {}", "Error:".red().bold(), e),
+        }
+        if executor.interrupted() {
+            return Ok(());
+        }
+
+        println!("{} Waiting for changes...\n", "→".blue());
+    }
+}
+
+/// The changed tests plus every test that transitively depends on one of them
+fn with_dependents(
+    changed: &HashSet<String>,
+    specs: &[(TestSpec, SpecExtensions)],
+) -> HashSet<String> {
+    let mut selected = changed.clone();
+
+    loop {
+        let before = selected.len();
+        for (test, _) in specs {
+            if test
+                .dependencies
+                .iter()
+                .any(|dependency| selected.contains(dependency))
+            {
+                selected.insert(test.name.clone());
+            }
+        }
+        if selected.len() == before {
+            return selected;
+        }
+    }
+}
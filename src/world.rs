@@ -0,0 +1,138 @@
+//! Server-global state a test can set up with `setup.world`, reading it back to restore it,
+//! and the dimensions tests run in
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Dimension tests run in unless they name another
+pub const OVERWORLD: &str = "minecraft:overworld";
+
+/// Namespaced id of a dimension, in the `minecraft` namespace when none is given
+pub fn dimension_id(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{}", name)
+    }
+}
+
+/// Lowest and highest buildable Y of the vanilla dimensions, unknown for custom ones
+pub fn build_height(dimension: &str) -> Option<(i32, i32)> {
+    match dimension {
+        "minecraft:overworld" => Some((-64, 319)),
+        "minecraft:the_nether" | "minecraft:the_end" => Some((0, 255)),
+        _ => None,
+    }
+}
+
+/// Time, weather, gamerules and difficulty applied before a test runs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldSetup {
+    /// Time of day in ticks, as for `/time set`
+    #[serde(default)]
+    pub time: Option<u32>,
+    #[serde(default)]
+    pub weather: Option<Weather>,
+    /// Gamerule names as the server knows them, e.g. `randomTickSpeed`
+    #[serde(default)]
+    pub gamerules: BTreeMap<String, GameruleValue>,
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+}
+
+impl WorldSetup {
+    /// Commands that apply the setup
+    pub fn commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        if let Some(time) = self.time {
+            commands.push(format!("time set {}", time));
+        }
+        if let Some(weather) = self.weather {
+            commands.push(format!("weather {}", weather.as_str()));
+        }
+        for (rule, value) in &self.gamerules {
+            commands.push(format!("gamerule {} {}", rule, value));
+        }
+        if let Some(difficulty) = self.difficulty {
+            commands.push(format!("difficulty {}", difficulty.as_str()));
+        }
+        commands
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl Weather {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Weather::Clear => "clear",
+            Weather::Rain => "rain",
+            Weather::Thunder => "thunder",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Peaceful,
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Difficulty::Peaceful => "peaceful",
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+/// Gamerules are either switches or numbers
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum GameruleValue {
+    Bool(bool),
+    Int(i64),
+}
+
+impl fmt::Display for GameruleValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameruleValue::Bool(value) => write!(f, "{}", value),
+            GameruleValue::Int(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Ticks from the reply to `time query daytime`, `gametime` or `day`: "The time is 1000"
+pub fn parse_time(message: &str) -> Option<String> {
+    let value = message.trim().strip_prefix("The time is ")?;
+    value.parse::<u64>().ok().map(|_| value.to_string())
+}
+
+/// Difficulty from the reply to `difficulty`: "The difficulty is Normal"
+pub fn parse_difficulty(message: &str) -> Option<String> {
+    let value = message.trim().strip_prefix("The difficulty is ")?;
+    Some(value.to_lowercase())
+}
+
+/// Value from the reply to `gamerule <rule>`: "Gamerule doFireTick is currently set to: true"
+pub fn parse_gamerule(message: &str, rule: &str) -> Option<String> {
+    let value = message
+        .trim()
+        .strip_prefix(&format!("Gamerule {} is currently set to: ", rule))?;
+    Some(value.to_string())
+}